@@ -0,0 +1,108 @@
+#ifndef RNCRYPTOR_H
+#define RNCRYPTOR_H
+
+/* Generated with cbindgen, do not edit by hand: run `cbindgen --config cbindgen.toml --output include/rncryptor.h` */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The length of both the encryption and the HMAC keys used by the key-based functions.
+#define RNCRYPTOR_KEY_LEN 32
+
+// The outcome of every `rncryptor_*` function, mirroring `v3::errors::ErrorKind`.
+typedef enum rncryptor_status {
+  RNCRYPTOR_STATUS_OK = 0,
+  // A required pointer argument was `NULL`.
+  RNCRYPTOR_STATUS_NULL_ARGUMENT = 1,
+  // The password was not valid UTF-8.
+  RNCRYPTOR_STATUS_INVALID_PASSWORD = 2,
+  RNCRYPTOR_STATUS_HMAC_GENERATION_FAILED = 3,
+  RNCRYPTOR_STATUS_HMAC_VALIDATION_FAILED = 4,
+  RNCRYPTOR_STATUS_HMAC_NOT_FOUND = 5,
+  RNCRYPTOR_STATUS_WRONG_INPUT_SIZE = 6,
+  RNCRYPTOR_STATUS_NOT_ENOUGH_INPUT = 7,
+  RNCRYPTOR_STATUS_BUFFER_TOO_SMALL = 8,
+  RNCRYPTOR_STATUS_IV_GENERATION_FAILED = 9,
+  RNCRYPTOR_STATUS_SALT_GENERATION_FAILED = 10,
+  RNCRYPTOR_STATUS_ENCRYPTION_FAILED = 11,
+  RNCRYPTOR_STATUS_DECRYPTION_FAILED = 12,
+  // Reading or writing a stream failed.
+  RNCRYPTOR_STATUS_IO = 13,
+  RNCRYPTOR_STATUS_INVALID_FORMAT = 14,
+  RNCRYPTOR_STATUS_LIMIT_EXCEEDED = 15,
+  RNCRYPTOR_STATUS_SIGNATURE_VALIDATION_FAILED = 16,
+  RNCRYPTOR_STATUS_TOKEN_EXPIRED = 17,
+  RNCRYPTOR_STATUS_TOKEN_NOT_YET_VALID = 18,
+  RNCRYPTOR_STATUS_KEY_GENERATION_FAILED = 19,
+} rncryptor_status;
+
+// A heap-allocated byte buffer handed over to C. Release it with `rncryptor_buffer_free`.
+typedef struct rncryptor_buffer {
+  uint8_t *data;
+  size_t len;
+} rncryptor_buffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Encrypts `plain_text_len` bytes with a NUL-terminated, UTF-8 `password`.
+//
+// # Safety
+// `password` must be a valid C string, `plain_text` must point to `plain_text_len` readable
+// bytes and `out` to a writable `RNCryptorBuffer`.
+enum rncryptor_status rncryptor_encrypt(const char *password,
+                                        const uint8_t *plain_text,
+                                        size_t plain_text_len,
+                                        struct rncryptor_buffer *out);
+
+// Decrypts a password-based message of `message_len` bytes.
+//
+// # Safety
+// `password` must be a valid C string, `message` must point to `message_len` readable
+// bytes and `out` to a writable `RNCryptorBuffer`.
+enum rncryptor_status rncryptor_decrypt(const char *password,
+                                        const uint8_t *message,
+                                        size_t message_len,
+                                        struct rncryptor_buffer *out);
+
+// Encrypts `plain_text_len` bytes with the key-based API, using a random `IV`.
+//
+// # Safety
+// `encryption_key` and `hmac_key` must point to `RNCRYPTOR_KEY_LEN` readable bytes each,
+// `plain_text` to `plain_text_len` readable bytes and `out` to a writable `RNCryptorBuffer`.
+enum rncryptor_status rncryptor_encrypt_with_keys(const uint8_t *encryption_key,
+                                                  const uint8_t *hmac_key,
+                                                  const uint8_t *plain_text,
+                                                  size_t plain_text_len,
+                                                  struct rncryptor_buffer *out);
+
+// Decrypts a key-based message of `message_len` bytes.
+//
+// # Safety
+// `encryption_key` and `hmac_key` must point to `RNCRYPTOR_KEY_LEN` readable bytes each,
+// `message` to `message_len` readable bytes and `out` to a writable `RNCryptorBuffer`.
+enum rncryptor_status rncryptor_decrypt_with_keys(const uint8_t *encryption_key,
+                                                  const uint8_t *hmac_key,
+                                                  const uint8_t *message,
+                                                  size_t message_len,
+                                                  struct rncryptor_buffer *out);
+
+// Zeroes and releases a buffer filled by one of the `rncryptor_*` functions. Calling it on an
+// empty (or already freed) buffer is a no-op.
+//
+// # Safety
+// `buffer` must be `NULL` or point to an `RNCryptorBuffer` filled by this library.
+void rncryptor_buffer_free(struct rncryptor_buffer *buffer);
+
+// Returns a static, NUL-terminated description of `status`.
+const char *rncryptor_status_description(enum rncryptor_status status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RNCRYPTOR_H */
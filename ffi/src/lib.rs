@@ -0,0 +1,275 @@
+/*!
+C bindings for [rncryptor](https://docs.rs/rncryptor/), built both as a `cdylib` and a `staticlib`.
+The matching header lives in `include/rncryptor.h` and is generated with
+[cbindgen](https://github.com/eqrion/cbindgen):
+
+```text
+cbindgen --config cbindgen.toml --output include/rncryptor.h
+```
+
+Every function returns a `RNCryptorStatus`, and on success fills an `RNCryptorBuffer` owned by
+the caller, which must be released with `rncryptor_buffer_free`.
+*/
+
+extern crate rncryptor;
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use rncryptor::v3;
+use rncryptor::v3::types::{EncryptionKey, HMACKey, IV};
+use rncryptor::v3::encryptor::Encryptor;
+use rncryptor::v3::decryptor::Decryptor;
+use rncryptor::v3::errors::{Error, ErrorKind};
+
+/// The length of both the encryption and the HMAC keys used by the key-based functions.
+pub const RNCRYPTOR_KEY_LEN: usize = 32;
+
+/// The outcome of every `rncryptor_*` function, mirroring `v3::errors::ErrorKind`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RNCryptorStatus {
+    Ok = 0,
+    /// A required pointer argument was `NULL`.
+    NullArgument = 1,
+    /// The password was not valid UTF-8.
+    InvalidPassword = 2,
+    HMACGenerationFailed = 3,
+    HMACValidationFailed = 4,
+    HMACNotFound = 5,
+    WrongInputSize = 6,
+    NotEnoughInput = 7,
+    BufferTooSmall = 8,
+    IVGenerationFailed = 9,
+    SaltGenerationFailed = 10,
+    EncryptionFailed = 11,
+    DecryptionFailed = 12,
+    /// Reading or writing a stream failed.
+    Io = 13,
+    InvalidFormat = 14,
+    LimitExceeded = 15,
+    SignatureValidationFailed = 16,
+    TokenExpired = 17,
+    TokenNotYetValid = 18,
+    KeyGenerationFailed = 19,
+}
+
+impl<'a> From<&'a ErrorKind> for RNCryptorStatus {
+    fn from(kind: &'a ErrorKind) -> RNCryptorStatus {
+        match *kind {
+            ErrorKind::HMACGenerationFailed => RNCryptorStatus::HMACGenerationFailed,
+            ErrorKind::HMACValidationFailed => RNCryptorStatus::HMACValidationFailed,
+            ErrorKind::HMACNotFound => RNCryptorStatus::HMACNotFound,
+            ErrorKind::WrongInputSize(_) => RNCryptorStatus::WrongInputSize,
+            ErrorKind::NotEnoughInput(_) => RNCryptorStatus::NotEnoughInput,
+            ErrorKind::BufferTooSmall(_) => RNCryptorStatus::BufferTooSmall,
+            ErrorKind::IVGenerationFailed(_) => RNCryptorStatus::IVGenerationFailed,
+            ErrorKind::SaltGenerationFailed(_) => RNCryptorStatus::SaltGenerationFailed,
+            ErrorKind::KeyGenerationFailed(_) => RNCryptorStatus::KeyGenerationFailed,
+            ErrorKind::EncryptionFailed(_) => RNCryptorStatus::EncryptionFailed,
+            ErrorKind::DecryptionFailed(_) => RNCryptorStatus::DecryptionFailed,
+            ErrorKind::Io(_) => RNCryptorStatus::Io,
+            ErrorKind::InvalidFormat => RNCryptorStatus::InvalidFormat,
+            ErrorKind::LimitExceeded(_) => RNCryptorStatus::LimitExceeded,
+            ErrorKind::SignatureValidationFailed => RNCryptorStatus::SignatureValidationFailed,
+            ErrorKind::TokenExpired => RNCryptorStatus::TokenExpired,
+            ErrorKind::TokenNotYetValid => RNCryptorStatus::TokenNotYetValid,
+        }
+    }
+}
+
+/// A heap-allocated byte buffer handed over to C. Release it with `rncryptor_buffer_free`.
+#[repr(C)]
+pub struct RNCryptorBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+fn write_result(result: Result<Vec<u8>, Error>, out: *mut RNCryptorBuffer) -> RNCryptorStatus {
+    match result {
+        Err(e) => RNCryptorStatus::from(&e.kind),
+        Ok(bytes) => {
+            let boxed = bytes.into_boxed_slice();
+            let len = boxed.len();
+            let data = Box::into_raw(boxed) as *mut u8;
+            unsafe {
+                (*out).data = data;
+                (*out).len = len;
+            }
+            RNCryptorStatus::Ok
+        }
+    }
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if len == 0 {
+        Some(&[])
+    } else if data.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(data, len))
+    }
+}
+
+unsafe fn keys(encryption_key: *const u8, hmac_key: *const u8) -> Option<(EncryptionKey, HMACKey)> {
+    if encryption_key.is_null() || hmac_key.is_null() {
+        return None;
+    }
+    let ek = slice::from_raw_parts(encryption_key, RNCRYPTOR_KEY_LEN).to_vec();
+    let hk = slice::from_raw_parts(hmac_key, RNCRYPTOR_KEY_LEN).to_vec();
+    Some((EncryptionKey::from(ek), HMACKey::from(hk)))
+}
+
+macro_rules! check_not_null {
+    ($e:expr) => {
+        match $e {
+            Some(v) => v,
+            None => return RNCryptorStatus::NullArgument,
+        }
+    };
+}
+
+unsafe fn password<'a>(password: *const c_char) -> Result<&'a str, RNCryptorStatus> {
+    if password.is_null() {
+        return Err(RNCryptorStatus::NullArgument);
+    }
+    CStr::from_ptr(password).to_str().map_err(|_| RNCryptorStatus::InvalidPassword)
+}
+
+/// Encrypts `plain_text_len` bytes with a NUL-terminated, UTF-8 `password`.
+///
+/// # Safety
+/// `password` must be a valid C string, `plain_text` must point to `plain_text_len` readable
+/// bytes and `out` to a writable `RNCryptorBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn rncryptor_encrypt(password: *const c_char,
+                                           plain_text: *const u8,
+                                           plain_text_len: usize,
+                                           out: *mut RNCryptorBuffer)
+                                           -> RNCryptorStatus {
+    let password = match self::password(password) {
+        Ok(p) => p,
+        Err(status) => return status,
+    };
+    let plain_text = check_not_null!(bytes(plain_text, plain_text_len));
+    if out.is_null() {
+        return RNCryptorStatus::NullArgument;
+    }
+    write_result(v3::encrypt(password, plain_text), out)
+}
+
+/// Decrypts a password-based message of `message_len` bytes.
+///
+/// # Safety
+/// `password` must be a valid C string, `message` must point to `message_len` readable
+/// bytes and `out` to a writable `RNCryptorBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn rncryptor_decrypt(password: *const c_char,
+                                           message: *const u8,
+                                           message_len: usize,
+                                           out: *mut RNCryptorBuffer)
+                                           -> RNCryptorStatus {
+    let password = match self::password(password) {
+        Ok(p) => p,
+        Err(status) => return status,
+    };
+    let message = check_not_null!(bytes(message, message_len));
+    if out.is_null() {
+        return RNCryptorStatus::NullArgument;
+    }
+    let result = Decryptor::from(password, message).and_then(|d| d.decrypt(message));
+    write_result(result, out)
+}
+
+/// Encrypts `plain_text_len` bytes with the key-based API, using a random `IV`.
+///
+/// # Safety
+/// `encryption_key` and `hmac_key` must point to `RNCRYPTOR_KEY_LEN` readable bytes each,
+/// `plain_text` to `plain_text_len` readable bytes and `out` to a writable `RNCryptorBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn rncryptor_encrypt_with_keys(encryption_key: *const u8,
+                                                     hmac_key: *const u8,
+                                                     plain_text: *const u8,
+                                                     plain_text_len: usize,
+                                                     out: *mut RNCryptorBuffer)
+                                                     -> RNCryptorStatus {
+    let (ek, hk) = check_not_null!(keys(encryption_key, hmac_key));
+    let plain_text = check_not_null!(bytes(plain_text, plain_text_len));
+    if out.is_null() {
+        return RNCryptorStatus::NullArgument;
+    }
+    let result = IV::new()
+        .and_then(|iv| Encryptor::from_keys(ek, hk, iv))
+        .and_then(|e| e.encrypt(plain_text));
+    write_result(result, out)
+}
+
+/// Decrypts a key-based message of `message_len` bytes.
+///
+/// # Safety
+/// `encryption_key` and `hmac_key` must point to `RNCRYPTOR_KEY_LEN` readable bytes each,
+/// `message` to `message_len` readable bytes and `out` to a writable `RNCryptorBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn rncryptor_decrypt_with_keys(encryption_key: *const u8,
+                                                     hmac_key: *const u8,
+                                                     message: *const u8,
+                                                     message_len: usize,
+                                                     out: *mut RNCryptorBuffer)
+                                                     -> RNCryptorStatus {
+    let (ek, hk) = check_not_null!(keys(encryption_key, hmac_key));
+    let message = check_not_null!(bytes(message, message_len));
+    if out.is_null() {
+        return RNCryptorStatus::NullArgument;
+    }
+    let result = Decryptor::from_keys(ek, hk, message).and_then(|d| d.decrypt(message));
+    write_result(result, out)
+}
+
+/// Zeroes and releases a buffer filled by one of the `rncryptor_*` functions. Calling it on an
+/// empty (or already freed) buffer is a no-op.
+///
+/// # Safety
+/// `buffer` must be `NULL` or point to an `RNCryptorBuffer` filled by this library.
+#[no_mangle]
+pub unsafe extern "C" fn rncryptor_buffer_free(buffer: *mut RNCryptorBuffer) {
+    if buffer.is_null() || (*buffer).data.is_null() {
+        return;
+    }
+    let mut boxed = Box::from_raw(ptr::slice_from_raw_parts_mut((*buffer).data, (*buffer).len));
+    for b in boxed.iter_mut() {
+        ptr::write_volatile(b, 0);
+    }
+    drop(boxed);
+    (*buffer).data = ptr::null_mut();
+    (*buffer).len = 0;
+}
+
+/// Returns a static, NUL-terminated description of `status`.
+#[no_mangle]
+pub extern "C" fn rncryptor_status_description(status: RNCryptorStatus) -> *const c_char {
+    let description: &'static [u8] = match status {
+        RNCryptorStatus::Ok => b"Success\0",
+        RNCryptorStatus::NullArgument => b"A required argument was NULL\0",
+        RNCryptorStatus::InvalidPassword => b"The password is not valid UTF-8\0",
+        RNCryptorStatus::HMACGenerationFailed => b"The generation of the HMAC failed\0",
+        RNCryptorStatus::HMACValidationFailed => b"HMAC mismatch\0",
+        RNCryptorStatus::HMACNotFound => b"The HMAC wasn't found inside the message\0",
+        RNCryptorStatus::WrongInputSize => b"The input size was wrong\0",
+        RNCryptorStatus::NotEnoughInput => b"Not enough input for decryption\0",
+        RNCryptorStatus::BufferTooSmall => b"The output buffer was too small\0",
+        RNCryptorStatus::IVGenerationFailed => b"The IV generation failed\0",
+        RNCryptorStatus::SaltGenerationFailed => b"The Salt generation failed\0",
+        RNCryptorStatus::EncryptionFailed => b"The encryption failed\0",
+        RNCryptorStatus::DecryptionFailed => b"The decryption failed\0",
+        RNCryptorStatus::Io => b"Reading or writing a stream failed\0",
+        RNCryptorStatus::InvalidFormat => b"The input isn't laid out as expected\0",
+        RNCryptorStatus::LimitExceeded => b"The output would exceed the given limit\0",
+        RNCryptorStatus::SignatureValidationFailed => b"Signature mismatch, or untrusted signer\0",
+        RNCryptorStatus::TokenExpired => b"The token has expired\0",
+        RNCryptorStatus::TokenNotYetValid => b"The token was issued in the future\0",
+        RNCryptorStatus::KeyGenerationFailed => b"The key generation failed\0",
+    };
+    description.as_ptr() as *const c_char
+}
@@ -0,0 +1,109 @@
+/* Round-trips data through the C API, and checks a spec test vector. */
+#include <stdio.h>
+#include <string.h>
+
+#include "rncryptor.h"
+
+#define CHECK(cond)                                                        \
+  do {                                                                     \
+    if (!(cond)) {                                                         \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+      return 1;                                                            \
+    }                                                                      \
+  } while (0)
+
+static int password_roundtrip(void) {
+  const char *secret = "secret data";
+  rncryptor_buffer encrypted = {NULL, 0};
+  rncryptor_buffer decrypted = {NULL, 0};
+  rncryptor_buffer wrong = {NULL, 0};
+
+  CHECK(rncryptor_encrypt("password", (const uint8_t *)secret, strlen(secret), &encrypted) ==
+        RNCRYPTOR_STATUS_OK);
+  CHECK(encrypted.len > strlen(secret));
+  CHECK(encrypted.data[0] == 3 && encrypted.data[1] == 1);
+
+  CHECK(rncryptor_decrypt("password", encrypted.data, encrypted.len, &decrypted) ==
+        RNCRYPTOR_STATUS_OK);
+  CHECK(decrypted.len == strlen(secret));
+  CHECK(memcmp(decrypted.data, secret, decrypted.len) == 0);
+
+  CHECK(rncryptor_decrypt("wrong", encrypted.data, encrypted.len, &wrong) ==
+        RNCRYPTOR_STATUS_HMAC_VALIDATION_FAILED);
+  CHECK(wrong.data == NULL);
+
+  rncryptor_buffer_free(&encrypted);
+  rncryptor_buffer_free(&decrypted);
+  CHECK(encrypted.data == NULL && encrypted.len == 0);
+  return 0;
+}
+
+static int key_roundtrip(void) {
+  uint8_t encryption_key[RNCRYPTOR_KEY_LEN];
+  uint8_t hmac_key[RNCRYPTOR_KEY_LEN];
+  const uint8_t plain_text[] = {0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16};
+  rncryptor_buffer encrypted = {NULL, 0};
+  rncryptor_buffer decrypted = {NULL, 0};
+  size_t i;
+
+  for (i = 0; i < RNCRYPTOR_KEY_LEN; i++) {
+    encryption_key[i] = (uint8_t)i;
+    hmac_key[i] = (uint8_t)(i + 1);
+  }
+
+  CHECK(rncryptor_encrypt_with_keys(encryption_key, hmac_key, plain_text, sizeof(plain_text),
+                                    &encrypted) == RNCRYPTOR_STATUS_OK);
+  CHECK(encrypted.data[0] == 3 && encrypted.data[1] == 0);
+  CHECK(rncryptor_decrypt_with_keys(encryption_key, hmac_key, encrypted.data, encrypted.len,
+                                    &decrypted) == RNCRYPTOR_STATUS_OK);
+  CHECK(decrypted.len == sizeof(plain_text));
+  CHECK(memcmp(decrypted.data, plain_text, sizeof(plain_text)) == 0);
+
+  encrypted.data[encrypted.len - 1] ^= 1;
+  rncryptor_buffer_free(&decrypted);
+  CHECK(rncryptor_decrypt_with_keys(encryption_key, hmac_key, encrypted.data, encrypted.len,
+                                    &decrypted) == RNCRYPTOR_STATUS_HMAC_VALIDATION_FAILED);
+
+  rncryptor_buffer_free(&encrypted);
+  return 0;
+}
+
+/* "One byte" from vectors/v3/password. */
+static int password_vector(void) {
+  const uint8_t message[] = {
+      0x03, 0x01, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x01, 0x02, 0x03, 0x04,
+      0x05, 0x06, 0x07, 0x08, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,
+      0x0c, 0x0d, 0x0e, 0x0f, 0x00, 0x01, 0xa1, 0xf8, 0x73, 0x0e, 0x0b, 0xf4, 0x80, 0xeb,
+      0x7b, 0x70, 0xf6, 0x90, 0xab, 0xf2, 0x1e, 0x02, 0x95, 0x14, 0x16, 0x4a, 0xd3, 0xc4,
+      0x74, 0xa5, 0x1b, 0x30, 0xc7, 0xea, 0xa1, 0xca, 0x54, 0x5b, 0x7d, 0xe3, 0xde, 0x5b,
+      0x01, 0x0a, 0xcb, 0xad, 0x0a, 0x9a, 0x13, 0x85, 0x7d, 0xf6, 0x96, 0xa8};
+  rncryptor_buffer decrypted = {NULL, 0};
+
+  CHECK(rncryptor_decrypt("thepassword", message, sizeof(message), &decrypted) ==
+        RNCRYPTOR_STATUS_OK);
+  CHECK(decrypted.len == 1 && decrypted.data[0] == 0x01);
+  rncryptor_buffer_free(&decrypted);
+  return 0;
+}
+
+static int errors(void) {
+  rncryptor_buffer out = {NULL, 0};
+  const uint8_t short_message[] = {3, 1, 0};
+
+  CHECK(rncryptor_encrypt(NULL, NULL, 0, &out) == RNCRYPTOR_STATUS_NULL_ARGUMENT);
+  CHECK(rncryptor_decrypt("password", short_message, sizeof(short_message), &out) ==
+        RNCRYPTOR_STATUS_NOT_ENOUGH_INPUT);
+  CHECK(strcmp(rncryptor_status_description(RNCRYPTOR_STATUS_HMAC_VALIDATION_FAILED),
+               "HMAC mismatch") == 0);
+  rncryptor_buffer_free(NULL);
+  rncryptor_buffer_free(&out);
+  return 0;
+}
+
+int main(void) {
+  if (password_roundtrip() || key_roundtrip() || password_vector() || errors()) {
+    return 1;
+  }
+  printf("ok\n");
+  return 0;
+}
@@ -0,0 +1,58 @@
+//! Compiles `tests/c/roundtrip.c` against `include/rncryptor.h` and the static library, then
+//! runs it.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn target_dir() -> PathBuf {
+    // The test binary lives in `target/<profile>/deps`.
+    let exe = env::current_exe().unwrap();
+    exe.parent().and_then(Path::parent).unwrap().to_path_buf()
+}
+
+/// `cargo test` only builds the library as an `rlib`, so build the `staticlib` explicitly.
+fn build_static_lib(manifest_dir: &Path, target_dir: &Path) -> PathBuf {
+    let mut cargo = Command::new(env!("CARGO"));
+    cargo.arg("build")
+        .arg("--lib")
+        .arg("--manifest-path")
+        .arg(manifest_dir.join("Cargo.toml"));
+    if target_dir.ends_with("release") {
+        cargo.arg("--release");
+    }
+    let status = cargo.status().expect("failed to run cargo");
+    assert!(status.success(), "building the static library failed");
+    target_dir.join("librncryptor_ffi.a")
+}
+
+#[test]
+fn c_roundtrip() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let static_lib = build_static_lib(&manifest_dir, &target_dir());
+    assert!(static_lib.exists(), "{} not found", static_lib.display());
+
+    let out_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let exe = out_dir.join("rncryptor_c_roundtrip");
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+
+    let status = Command::new(cc)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/c/roundtrip.c"))
+        .arg(&static_lib)
+        .args(["-lpthread", "-ldl", "-lm"])
+        .arg("-o")
+        .arg(&exe)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "compiling the C test program failed");
+
+    let output = Command::new(&exe).output().unwrap();
+    assert!(output.status.success(),
+            "C test program failed: {}",
+            String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,167 @@
+//! Python bindings for [rncryptor](https://docs.rs/rncryptor/), built with
+//! [maturin](https://www.maturin.rs/):
+//!
+//! ```text
+//! cd python && maturin develop && pytest
+//! ```
+//!
+//! Every `rncryptor::v3::errors::ErrorKind` is mapped to a subclass of `rncryptor.RNCryptorError`.
+//! PBKDF2 and AES run with the GIL released, so they can be parallelised with threads.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyIOError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use rncryptor::v3;
+use rncryptor::v3::decryptor::Decryptor;
+use rncryptor::v3::encryptor::Encryptor;
+use rncryptor::v3::errors::{Error, ErrorKind};
+use rncryptor::v3::kdf;
+use rncryptor::v3::stream::{self, MessageReader};
+use rncryptor::v3::types::{Credentials, EncryptionKey, HMACKey, Salt, IV};
+
+create_exception!(rncryptor, RNCryptorError, PyException, "Base class of every RNCryptor error.");
+create_exception!(rncryptor, HMACError, RNCryptorError, "The HMAC could not be generated or validated.");
+create_exception!(rncryptor, InvalidInputError, RNCryptorError, "The input has the wrong size, or is too short.");
+create_exception!(rncryptor, RandomGenerationError, RNCryptorError, "A salt or an IV could not be generated.");
+create_exception!(rncryptor, EncryptionError, RNCryptorError, "The underlying cipher failed to encrypt.");
+create_exception!(rncryptor, DecryptionError, RNCryptorError, "The underlying cipher failed to decrypt.");
+create_exception!(rncryptor, SignatureError, RNCryptorError, "The signature didn't match, or wasn't made by a trusted key.");
+create_exception!(rncryptor, TokenError, RNCryptorError, "The token has expired, or was issued in the future.");
+
+fn to_py_err(e: Error) -> PyErr {
+    let message = format!("{} ({:?})", e.message, e.kind);
+    match e.kind {
+        ErrorKind::HMACGenerationFailed |
+        ErrorKind::HMACValidationFailed |
+        ErrorKind::HMACNotFound => HMACError::new_err(message),
+        ErrorKind::WrongInputSize(_) |
+        ErrorKind::NotEnoughInput(_) |
+        ErrorKind::BufferTooSmall(_) |
+        ErrorKind::InvalidFormat |
+        ErrorKind::LimitExceeded(_) => InvalidInputError::new_err(message),
+        ErrorKind::IVGenerationFailed(_) |
+        ErrorKind::SaltGenerationFailed(_) |
+        ErrorKind::KeyGenerationFailed(_) => RandomGenerationError::new_err(message),
+        ErrorKind::EncryptionFailed(_) => EncryptionError::new_err(message),
+        ErrorKind::DecryptionFailed(_) => DecryptionError::new_err(message),
+        ErrorKind::SignatureValidationFailed => SignatureError::new_err(message),
+        ErrorKind::TokenExpired |
+        ErrorKind::TokenNotYetValid => TokenError::new_err(message),
+        ErrorKind::Io(e) => PyIOError::new_err(e.to_string()),
+    }
+}
+
+fn keys(encryption_key: &[u8], hmac_key: &[u8]) -> PyResult<(EncryptionKey, HMACKey)> {
+    if encryption_key.len() != kdf::KEY_LEN || hmac_key.len() != kdf::KEY_LEN {
+        return Err(InvalidInputError::new_err(format!("keys must be {} bytes long", kdf::KEY_LEN)));
+    }
+    Ok((EncryptionKey::from(encryption_key.to_vec()), HMACKey::from(hmac_key.to_vec())))
+}
+
+fn bytes(py: Python<'_>, result: Result<Vec<u8>, Error>) -> PyResult<Py<PyBytes>> {
+    result.map(|v| PyBytes::new(py, &v).unbind()).map_err(to_py_err)
+}
+
+/// Encrypts `data` with `password`, returning a v3 password-based message.
+#[pyfunction]
+fn encrypt(py: Python<'_>, password: &str, data: &[u8]) -> PyResult<Py<PyBytes>> {
+    let result = py.detach(|| v3::encrypt(password, data));
+    bytes(py, result)
+}
+
+/// Decrypts a v3 password-based `message` with `password`.
+#[pyfunction]
+fn decrypt(py: Python<'_>, password: &str, message: &[u8]) -> PyResult<Py<PyBytes>> {
+    let result = py.detach(|| v3::decrypt(password, &message.to_vec()));
+    bytes(py, result)
+}
+
+/// Encrypts `data` with two 32 bytes keys, returning a v3 key-based message.
+#[pyfunction]
+fn encrypt_with_keys(py: Python<'_>,
+                     encryption_key: &[u8],
+                     hmac_key: &[u8],
+                     data: &[u8])
+                     -> PyResult<Py<PyBytes>> {
+    let (ek, hk) = keys(encryption_key, hmac_key)?;
+    let result = py.detach(|| {
+        IV::new()
+            .and_then(|iv| Encryptor::from_keys(ek, hk, iv))
+            .and_then(|e| e.encrypt(data))
+    });
+    bytes(py, result)
+}
+
+/// Decrypts a v3 key-based `message` with two 32 bytes keys.
+#[pyfunction]
+fn decrypt_with_keys(py: Python<'_>,
+                     encryption_key: &[u8],
+                     hmac_key: &[u8],
+                     message: &[u8])
+                     -> PyResult<Py<PyBytes>> {
+    let (ek, hk) = keys(encryption_key, hmac_key)?;
+    let result = py.detach(|| Decryptor::from_keys(ek, hk, message).and_then(|d| d.decrypt(message)));
+    bytes(py, result)
+}
+
+/// Derives a 32 bytes key from `password` and `salt` with PBKDF2-HMAC-SHA1.
+#[pyfunction]
+#[pyo3(signature = (password, salt, iterations = kdf::DEFAULT_ITERATIONS))]
+fn derive_key(py: Python<'_>, password: &[u8], salt: &[u8], iterations: u32) -> Py<PyBytes> {
+    let salt = Salt(salt.to_vec());
+    let key = py.detach(|| kdf::derive_key_with_iterations(password, &salt, iterations));
+    PyBytes::new(py, &key).unbind()
+}
+
+/// Encrypts the file at `src` with `password` into `dst`, streaming both files.
+#[pyfunction]
+fn encrypt_file(py: Python<'_>, password: &str, src: &str, dst: &str) -> PyResult<()> {
+    let src = File::open(src).map_err(|e| PyIOError::new_err(e.to_string()))?;
+    let dst = File::create(dst).map_err(|e| PyIOError::new_err(e.to_string()))?;
+    let credentials = Credentials::Password(password);
+    py.detach(|| stream::encrypt(&credentials, BufReader::new(src), BufWriter::new(dst)))
+        .map(|_| ())
+        .map_err(to_py_err)
+}
+
+/// Decrypts the file at `src` with `password` into `dst`, streaming both files. `dst` isn't
+/// even created if the HMAC validation fails.
+#[pyfunction]
+fn decrypt_file(py: Python<'_>, password: &str, src: &str, dst: &str) -> PyResult<()> {
+    let src = File::open(src).map_err(|e| PyIOError::new_err(e.to_string()))?;
+    let credentials = Credentials::Password(password);
+    let mut message = py.detach(|| {
+            let mut message = MessageReader::new(&credentials, BufReader::new(src))?;
+            message.verify().map(|_| message)
+        })
+        .map_err(to_py_err)?;
+    let dst = File::create(dst).map_err(|e| PyIOError::new_err(e.to_string()))?;
+    py.detach(|| message.decrypt_to(BufWriter::new(dst))).map(|_| ()).map_err(to_py_err)
+}
+
+#[pymodule(name = "rncryptor")]
+fn rncryptor_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("RNCryptorError", py.get_type::<RNCryptorError>())?;
+    m.add("HMACError", py.get_type::<HMACError>())?;
+    m.add("InvalidInputError", py.get_type::<InvalidInputError>())?;
+    m.add("RandomGenerationError", py.get_type::<RandomGenerationError>())?;
+    m.add("EncryptionError", py.get_type::<EncryptionError>())?;
+    m.add("DecryptionError", py.get_type::<DecryptionError>())?;
+    m.add("SignatureError", py.get_type::<SignatureError>())?;
+    m.add("TokenError", py.get_type::<TokenError>())?;
+    m.add("KEY_LEN", kdf::KEY_LEN)?;
+    m.add_function(wrap_pyfunction!(encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(encrypt_with_keys, m)?)?;
+    m.add_function(wrap_pyfunction!(decrypt_with_keys, m)?)?;
+    m.add_function(wrap_pyfunction!(derive_key, m)?)?;
+    m.add_function(wrap_pyfunction!(encrypt_file, m)?)?;
+    m.add_function(wrap_pyfunction!(decrypt_file, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,93 @@
+"""Tests for the `rncryptor` extension module, run with `maturin develop && pytest`."""
+
+import os
+
+import pytest
+
+import rncryptor
+
+VECTORS = os.path.join(os.path.dirname(__file__), "..", "..", "vectors", "v3")
+
+
+def load_vectors(name):
+    """Parses one of the RNCryptor-Spec vector files into a list of dicts."""
+    records, current = [], {}
+    with open(os.path.join(VECTORS, name), encoding="utf-8") as f:
+        for line in f:
+            line = line.rstrip("\n")
+            if not line.strip():
+                if current:
+                    records.append(current)
+                current = {}
+                continue
+            if line.startswith("#"):
+                continue
+            key, _, value = line.partition(":")
+            value = value.strip()
+            if key.endswith("_hex"):
+                value = bytes.fromhex(value.replace(" ", ""))
+            current[key.strip()] = value
+    if current:
+        records.append(current)
+    return [r for r in records if r["version"] == "3"]
+
+
+@pytest.mark.parametrize("v", load_vectors("kdf"), ids=lambda v: v["title"])
+def test_kdf_vectors(v):
+    assert rncryptor.derive_key(v["password"].encode(), v["salt_hex"]) == v["key_hex"]
+
+
+@pytest.mark.parametrize("v", load_vectors("key"), ids=lambda v: v["title"])
+def test_key_vectors(v):
+    plain_text = rncryptor.decrypt_with_keys(v["enc_key_hex"], v["hmac_key_hex"], v["ciphertext_hex"])
+    assert plain_text == v["plaintext_hex"]
+
+
+@pytest.mark.parametrize("v", load_vectors("password"), ids=lambda v: v["title"])
+def test_password_vectors(v):
+    assert rncryptor.decrypt(v["password"], v["ciphertext_hex"]) == v["plaintext_hex"]
+
+
+def test_password_roundtrip():
+    message = rncryptor.encrypt("password", b"secret")
+    assert message[:2] == b"\x03\x01"
+    assert rncryptor.decrypt("password", message) == b"secret"
+
+
+def test_key_roundtrip():
+    ek, hk = os.urandom(rncryptor.KEY_LEN), os.urandom(rncryptor.KEY_LEN)
+    message = rncryptor.encrypt_with_keys(ek, hk, b"")
+    assert message[:2] == b"\x03\x00"
+    assert rncryptor.decrypt_with_keys(ek, hk, message) == b""
+
+
+def test_derive_key_iterations():
+    salt = bytes.fromhex("0203040506070801")
+    assert rncryptor.derive_key(b"thepassword", salt, 1000) != rncryptor.derive_key(b"thepassword", salt)
+
+
+def test_wrong_password_raises_hmac_error():
+    message = rncryptor.encrypt("password", b"secret")
+    with pytest.raises(rncryptor.HMACError):
+        rncryptor.decrypt("wrong", message)
+
+
+def test_errors_share_a_base_class():
+    with pytest.raises(rncryptor.RNCryptorError):
+        rncryptor.decrypt("password", b"\x03\x01")
+    with pytest.raises(rncryptor.InvalidInputError):
+        rncryptor.decrypt("password", b"\x03\x01")
+    with pytest.raises(rncryptor.InvalidInputError):
+        rncryptor.encrypt_with_keys(b"short", b"short", b"data")
+
+
+def test_file_helpers(tmp_path):
+    src, enc, dec = tmp_path / "plain", tmp_path / "plain.enc", tmp_path / "plain.dec"
+    src.write_bytes(b"some file content" * 100)
+    rncryptor.encrypt_file("password", str(src), str(enc))
+    rncryptor.decrypt_file("password", str(enc), str(dec))
+    assert dec.read_bytes() == src.read_bytes()
+
+    with pytest.raises(rncryptor.HMACError):
+        rncryptor.decrypt_file("wrong", str(enc), str(tmp_path / "never"))
+    assert not (tmp_path / "never").exists()
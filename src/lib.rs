@@ -55,9 +55,26 @@ match e {
 }
 ```
 
+## `no_std` support
+The core of the library (header parsing, key derivation, CBC + HMAC and the in-memory
+`Encryptor::encrypt_into`/`Decryptor::decrypt_into` functions) only needs `alloc`. Disable the
+default `std` feature to use it on embedded targets; the functions relying on the OS random number
+generator (like `v3::encrypt`, `Salt::new` and `IV::new`) then need to be replaced by their
+`_with_rng` counterparts.
+
+```toml
+[dependencies]
+rncryptor = { version = "0.1", default-features = false }
+```
+
 */
 
+#![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+#[macro_use]
+extern crate alloc;
 
 pub mod v3;
 
@@ -0,0 +1,50 @@
+//! Encryption binding caller-supplied associated data (a database row id, a file path...) to
+//! the `Message`, so that a `Message` copied elsewhere fails to decrypt.
+//!
+//! # Interoperability
+//!
+//! **This is an extension, not part of the v3 spec.** The `Message` looks like any other v3
+//! message, but its HMAC also covers the associated data, followed by its length (u64, BE):
+//! stock RNCryptor implementations (and `v3::decrypt`) reject it with an HMAC mismatch, and it
+//! can only be decrypted by `aad::decrypt`, with the very same associated data. The associated
+//! data itself isn't stored in the `Message`.
+
+use alloc::vec::Vec;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::decryptor::Decryptor;
+use v3::errors::Result;
+use v3::rng::{RngCore, CryptoRng};
+#[cfg(feature = "std")]
+use v3::rng::OsRng;
+
+/// Encrypts `plain_text`, binding it to `associated_data`, drawing the salts (if needed) and the
+/// `IV` from the given random number generator.
+pub fn encrypt_with_rng<R: RngCore + CryptoRng>(credentials: &Credentials,
+                                                plain_text: &PlainText,
+                                                associated_data: &[u8],
+                                                rng: &mut R)
+                                                -> Result<Message> {
+    Encryptor::from_credentials_with_rng(credentials, rng)?
+        .encrypt_with_associated_data(plain_text, associated_data)
+}
+
+/// Encrypts `plain_text`, binding it to `associated_data`.
+#[cfg(feature = "std")]
+pub fn encrypt(credentials: &Credentials,
+               plain_text: &PlainText,
+               associated_data: &[u8])
+               -> Result<Message> {
+    encrypt_with_rng(credentials, plain_text, associated_data, &mut OsRng)
+}
+
+/// Decrypts a `Message` produced by `aad::encrypt`, failing with
+/// `ErrorKind::HMACValidationFailed` unless `associated_data` is the same.
+pub fn decrypt(credentials: &Credentials,
+               message: &Message,
+               associated_data: &[u8])
+               -> Result<Vec<u8>> {
+    Decryptor::from_credentials(credentials, message)?
+        .decrypt_with_associated_data(message, associated_data)
+}
@@ -0,0 +1,187 @@
+//! Encryption of whole directory trees, as tar archives wrapped in a single v3 `Message`.
+//!
+//! The archive is streamed through the encryptor as it's built, and extracted while it's
+//! decrypted, so no plain text archive ever hits the disk. Only regular files and directories
+//! are archived and extracted: symbolic links (which could point outside of the tree) and special
+//! files are refused both ways.
+
+extern crate tar;
+
+use std::fs;
+use std::io::{self, Read, Write, Seek};
+use std::path::{Component, Path};
+use std::borrow::ToOwned;
+use std::format;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::rng::{RngCore, CryptoRng, OsRng};
+use v3::stream::{EncryptWriter, MessageReader};
+
+/// Archives the content of `dir` and encrypts it into `writer`, drawing the salts and the `IV`
+/// from the given random number generator. Returns the underlying writer.
+///
+/// Fails with `ErrorKind::InvalidFormat` if `dir` holds anything but regular files and
+/// directories, such as symbolic links.
+pub fn encrypt_dir_with_rng<P, W, G>(credentials: &Credentials,
+                                     dir: P,
+                                     writer: W,
+                                     rng: &mut G)
+                                     -> Result<W>
+    where P: AsRef<Path>,
+          W: Write,
+          G: RngCore + CryptoRng
+{
+    check_files_and_dirs(dir.as_ref())?;
+    let encryptor = Encryptor::from_credentials_with_rng(credentials, rng)?;
+    let mut builder = tar::Builder::new(EncryptWriter::new(&encryptor, writer)?);
+    // Should a link appear after the check, it's archived as such, and refused at extraction.
+    builder.follow_symlinks(false);
+    builder.append_dir_all(".", dir)?;
+    builder.into_inner()?.finish()
+}
+
+/// Archives the content of `dir` and encrypts it into `writer`. Returns the underlying writer.
+pub fn encrypt_dir<P: AsRef<Path>, W: Write>(credentials: &Credentials,
+                                             dir: P,
+                                             writer: W)
+                                             -> Result<W> {
+    encrypt_dir_with_rng(credentials, dir, writer, &mut OsRng)
+}
+
+/// Checks that the tree under `dir` only holds regular files and directories.
+fn check_files_and_dirs(dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let file_type = fs::symlink_metadata(&path)?.file_type();
+        if file_type.is_dir() {
+            check_files_and_dirs(&path)?;
+        } else if !file_type.is_file() {
+            return Err(Error::new(ErrorKind::InvalidFormat,
+                                  format!("Refusing to archive {}, which is neither a file nor \
+                                           a directory.",
+                                          path.display())));
+        }
+    }
+    Ok(())
+}
+
+/// Tells apart the temporary directories of concurrent extractions.
+static EXTRACTIONS: AtomicUsize = AtomicUsize::new(0);
+
+fn is_relative_and_contained(path: &Path) -> bool {
+    path.components().all(|c| match c {
+        Component::Normal(_) | Component::CurDir => true,
+        Component::ParentDir | Component::RootDir | Component::Prefix(_) => false,
+    })
+}
+
+/// Validates the encrypted archive read from `reader`, then decrypts and extracts it into
+/// `dest`, which is created if needed.
+///
+/// Only regular files and directories are extracted, and only inside `dest`: entries with an
+/// absolute path or a `..` component, links and special files make the extraction fail with
+/// `ErrorKind::InvalidFormat`.
+///
+/// The archive is first extracted into a temporary directory inside `dest`. Its content is only
+/// moved into `dest` once the whole archive has been decrypted, authenticated and unpacked, and
+/// once none of its entries conflicts with the content of `dest` (a directory where `dest` has
+/// a file, or the other way around, reported as `io::ErrorKind::AlreadyExists`): nothing is
+/// written to `dest` if any of these steps fails. Files already in `dest` are replaced and
+/// directories merged. Should moving an entry still fail afterwards (for lack of permissions,
+/// say), the entries moved before it are left in `dest`.
+pub fn decrypt_dir<R, P>(credentials: &Credentials, reader: R, dest: P) -> Result<()>
+    where R: Read + Seek,
+          P: AsRef<Path>
+{
+    let plain_text = MessageReader::new(credentials, reader)?.into_plain_text()?;
+    let dest = dest.as_ref();
+    fs::create_dir_all(dest)?;
+
+    let temporary = dest.join(format!(".rncryptor-extract-{}-{}",
+                                      process::id(),
+                                      EXTRACTIONS.fetch_add(1, Ordering::Relaxed)));
+    fs::create_dir(&temporary)?;
+
+    let extracted = extract(plain_text, &temporary)
+        .and_then(|_| check_conflicts(&temporary, dest))
+        .and_then(|_| move_into(&temporary, dest));
+    let removed = fs::remove_dir_all(&temporary);
+    extracted?;
+    removed?;
+    Ok(())
+}
+
+/// Extracts the whole archive into `dest`, reading the plain text up to its end so that its
+/// authentication is complete.
+fn extract<R: Read>(plain_text: R, dest: &Path) -> Result<()> {
+    let mut archive = tar::Archive::new(plain_text);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let entry_type = entry.header().entry_type();
+
+        if !is_relative_and_contained(&path) {
+            return Err(Error::new(ErrorKind::InvalidFormat,
+                                  format!("Refusing to extract {}.", path.display())));
+        }
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            return Err(Error::new(ErrorKind::InvalidFormat,
+                                  format!("Refusing to extract {}, which is neither a file nor \
+                                           a directory.",
+                                          path.display())));
+        }
+        if !entry.unpack_in(dest)? {
+            return Err(Error::new(ErrorKind::InvalidFormat,
+                                  "Refusing to extract outside the destination.".to_owned()));
+        }
+    }
+    // The end of the archive is marked before the end of the plain text.
+    io::copy(&mut archive.into_inner(), &mut io::sink())?;
+    Ok(())
+}
+
+/// Fails if an entry of `from` can't be moved into `to` by `move_into`, as `to` holds something
+/// else than a directory where `from` has one, or a directory where `from` has a file.
+fn check_conflicts(from: &Path, to: &Path) -> Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        let target_is_dir = match fs::symlink_metadata(&target) {
+            Ok(metadata) => metadata.is_dir(),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        match (entry.file_type()?.is_dir(), target_is_dir) {
+            (true, true) => check_conflicts(&entry.path(), &target)?,
+            (false, false) => {}
+            _ => {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                                          format!("{} already exists, and isn't of the same type \
+                                                   as in the archive.",
+                                                  target.display()))
+                    .into())
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Moves the content of `from` into `to`, merging the directories which already exist there.
+fn move_into(from: &Path, to: &Path) -> Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        // Not following links, which could lead outside of `to`.
+        let target_is_dir = fs::symlink_metadata(&target).map(|m| m.is_dir()).unwrap_or(false);
+        if entry.file_type()?.is_dir() && target_is_dir {
+            move_into(&entry.path(), &target)?;
+        } else {
+            fs::rename(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Crash-safe replacement of whole files.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::borrow::ToOwned;
+
+use v3::errors::Result;
+
+/// Writes `bytes` to `path` through a temporary file, synced and then renamed over it, so that
+/// `path` only ever holds its previous or its new content, even after a crash. The temporary
+/// file is removed if anything fails before the rename.
+pub(crate) fn write(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+
+    let written = File::create(&temporary).and_then(|mut file| {
+        file.write_all(bytes)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&temporary, path)) {
+        let _ = fs::remove_file(&temporary);
+        return Err(e.into());
+    }
+    sync_parent(path)
+}
+
+/// Syncs the directory holding `path`, which makes its renaming durable.
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()?;
+    Ok(())
+}
+
+/// Directories can't be opened (nor synced) as files outside of Unix.
+#[cfg(not(unix))]
+fn sync_parent(_: &Path) -> Result<()> {
+    Ok(())
+}
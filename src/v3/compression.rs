@@ -0,0 +1,175 @@
+//! Compression of the plain text before encryption, as cipher texts are incompressible.
+//!
+//! The plain text sealed in the v3 `Message` starts with a marker,
+//! `"RNCZ" | algorithm (1) | uncompressed length (u64, BE)`, followed by the (possibly)
+//! compressed data. Being part of the plain text, the marker is authenticated by the HMAC like
+//! everything else. Messages produced here are regular v3 messages, but their plain text only
+//! makes sense to `compression::decrypt`, which in turn requires the marker.
+//!
+//! # Caveats
+//!
+//! Compression leaks information about the plain text through the length of the `Message`.
+//! Whenever attacker-controlled data is compressed together with secrets (think of a token
+//! echoed next to user input), an attacker able to observe lengths can recover the secrets one
+//! byte at a time, as in the CRIME and BREACH attacks on TLS and HTTP. Only compress payloads
+//! which don't mix secrets with data others can influence, or whose length isn't observable.
+//!
+//! Decompression is bounded by the `max_len` given to `decrypt`, so a malicious (but correctly
+//! authenticated) message can't exhaust the memory.
+
+#[cfg(feature = "deflate")]
+extern crate flate2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+
+use std::io::Read;
+#[cfg(feature = "deflate")]
+use std::io::Write;
+use std::vec::Vec;
+use std::borrow::ToOwned;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::decryptor::Decryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::rng::{RngCore, CryptoRng, OsRng};
+
+/// The magic bytes starting the plain text of every compressed `Message`.
+pub const MAGIC: &[u8; 4] = b"RNCZ";
+/// A sensible default for the `max_len` of `decrypt`: 64 MiB.
+pub const DEFAULT_MAX_LEN: usize = 64 * 1024 * 1024;
+
+const MARKER_LEN: usize = 4 + 1 + 8;
+
+/// The compression algorithms, each behind the feature of the same name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// Stored as is, which is also used whenever compressing doesn't pay off.
+    None,
+    /// Raw deflate (RFC 1951), at the default level.
+    #[cfg(feature = "deflate")]
+    Deflate,
+    /// Zstandard, at the default level.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    fn id(&self) -> u8 {
+        match *self {
+            Compression::None => 0,
+            #[cfg(feature = "deflate")]
+            Compression::Deflate => 1,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Compression> {
+        match id {
+            0 => Ok(Compression::None),
+            #[cfg(feature = "deflate")]
+            1 => Ok(Compression::Deflate),
+            #[cfg(feature = "zstd")]
+            2 => Ok(Compression::Zstd),
+            _ => {
+                Err(Error::new(ErrorKind::InvalidFormat,
+                               "Unknown or disabled compression algorithm.".to_owned()))
+            }
+        }
+    }
+
+    fn compress(&self, data: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        match *self {
+            Compression::None => out.extend_from_slice(data),
+            #[cfg(feature = "deflate")]
+            Compression::Deflate => {
+                let mut encoder = flate2::write::DeflateEncoder::new(out,
+                                                                     flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()?;
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                out.extend(zstd::stream::encode_all(data, zstd::DEFAULT_COMPRESSION_LEVEL)?)
+            }
+        }
+        Ok(())
+    }
+
+    /// Decompresses at most `limit` bytes of `data` into `out`.
+    fn decompress(&self, data: &[u8], limit: u64, out: &mut Vec<u8>) -> Result<()> {
+        match *self {
+            Compression::None => out.extend_from_slice(&data[..data.len().min(limit as usize)]),
+            #[cfg(feature = "deflate")]
+            Compression::Deflate => {
+                flate2::read::DeflateDecoder::new(data).take(limit).read_to_end(out)?;
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                zstd::stream::read::Decoder::new(data)?.take(limit).read_to_end(out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compresses `plain_text` with `compression` (unless that makes it bigger), then encrypts it,
+/// drawing the salts and the `IV` from the given random number generator.
+pub fn encrypt_with_rng<R: RngCore + CryptoRng>(credentials: &Credentials,
+                                                compression: Compression,
+                                                plain_text: &PlainText,
+                                                rng: &mut R)
+                                                -> Result<Message> {
+    let mut marked = Vec::with_capacity(MARKER_LEN + plain_text.len());
+    marked.extend_from_slice(MAGIC);
+    marked.push(compression.id());
+    marked.extend_from_slice(&(plain_text.len() as u64).to_be_bytes());
+    compression.compress(plain_text, &mut marked)?;
+
+    if marked.len() > MARKER_LEN + plain_text.len() {
+        marked.truncate(MARKER_LEN);
+        marked[4] = Compression::None.id();
+        marked.extend_from_slice(plain_text);
+    }
+
+    Encryptor::from_credentials_with_rng(credentials, rng)?.encrypt(&marked)
+}
+
+/// Compresses `plain_text` with `compression` (unless that makes it bigger), then encrypts it.
+pub fn encrypt(credentials: &Credentials,
+               compression: Compression,
+               plain_text: &PlainText)
+               -> Result<Message> {
+    encrypt_with_rng(credentials, compression, plain_text, &mut OsRng)
+}
+
+/// Decrypts a `Message` produced by `compression::encrypt`, then decompresses it.
+///
+/// Fails with `ErrorKind::LimitExceeded` if the plain text is longer than `max_len` bytes,
+/// before decompressing anything.
+pub fn decrypt(credentials: &Credentials, message: &Message, max_len: usize) -> Result<Vec<u8>> {
+    let marked = Decryptor::from_credentials(credentials, message)?.decrypt(message)?;
+    if marked.len() < MARKER_LEN || &marked[..4] != MAGIC {
+        return Err(Error::new(ErrorKind::InvalidFormat,
+                              "The message wasn't compressed by rncryptor.".to_owned()));
+    }
+
+    let compression = Compression::from_id(marked[4])?;
+    let mut len = [0; 8];
+    len.copy_from_slice(&marked[5..MARKER_LEN]);
+    let len = u64::from_be_bytes(len);
+    if len > max_len as u64 {
+        return Err(Error::new(ErrorKind::LimitExceeded(max_len),
+                              "The decompressed plain text would be too large.".to_owned()));
+    }
+
+    let mut plain_text = Vec::with_capacity(len as usize);
+    compression.decompress(&marked[MARKER_LEN..], len + 1, &mut plain_text)
+        .map_err(|_| Error::new(ErrorKind::InvalidFormat, "Corrupt compressed data.".to_owned()))?;
+    if plain_text.len() as u64 != len {
+        return Err(Error::new(ErrorKind::InvalidFormat,
+                              "The decompressed length doesn't match the marker.".to_owned()));
+    }
+    Ok(plain_text)
+}
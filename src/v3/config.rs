@@ -0,0 +1,554 @@
+//! Encrypted values inside JSON, TOML and YAML configuration files, each format behind the
+//! feature of the same name.
+//!
+//! A secret is stored in place of its value as `ENC[<base64 of a v3 Message>]`, so that the rest
+//! of the file stays readable and diffable:
+//!
+//! ```text
+//! [database]
+//! host = "db.internal"
+//! password = "ENC[AwEk...]"  # rotated every quarter
+//! ```
+//!
+//! The `decrypt_*` functions walk a parsed document and decrypt every such value (whatever its
+//! type, it decrypts to a string), while the `encrypt_*_keys` functions encrypt the values at the
+//! given dotted paths (`"database.password"`) directly in the text, leaving the formatting and
+//! the comments alone. Already encrypted values are left as they are, so encrypting is
+//! idempotent. The credentials typically come from the environment, see `Secret::from_env`.
+//!
+//! Paths only go through tables (mappings, objects): values inside arrays can be decrypted, but
+//! not selected for encryption. In YAML, only the values of block mappings written on a single
+//! line can be encrypted in place (no flow collections, block scalars, anchors or tags).
+
+extern crate base64;
+#[cfg(feature = "json")]
+extern crate serde_json;
+#[cfg(feature = "toml")]
+extern crate toml_edit;
+#[cfg(feature = "yaml")]
+extern crate serde_yaml;
+
+use std::env;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::io;
+#[cfg(any(feature = "json", feature = "yaml"))]
+use std::ops::Range;
+use std::string::{String, ToString};
+#[cfg(any(feature = "json", feature = "yaml"))]
+use std::vec::Vec;
+use std::borrow::ToOwned;
+
+use self::base64::Engine;
+use self::base64::engine::general_purpose::STANDARD;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::decryptor::Decryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::keys::KeyPair;
+use v3::rng::{RngCore, CryptoRng, OsRng};
+
+/// The environment variable holding the password, for `Secret::from_env`.
+pub const PASSWORD_VAR: &str = "RNCRYPTOR_PASSWORD";
+/// The environment variable holding a `KeyPair` (in hex or base64), for `Secret::from_env`.
+pub const KEYS_VAR: &str = "RNCRYPTOR_KEYS";
+
+const PREFIX: &str = "ENC[";
+const SUFFIX: &str = "]";
+
+fn invalid_format(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidFormat, message.to_owned())
+}
+
+/// An owned password or `KeyPair`, typically read from the environment. `Debug` never prints it.
+#[derive(Clone)]
+pub enum Secret {
+    Password(String),
+    Keys(KeyPair),
+}
+
+impl Secret {
+    /// Reads the `KeyPair` in `RNCRYPTOR_KEYS`, or else the password in `RNCRYPTOR_PASSWORD`.
+    /// Setting both is an error, as it's unclear which one is meant.
+    pub fn from_env() -> Result<Secret> {
+        match (env::var(KEYS_VAR), env::var(PASSWORD_VAR)) {
+            (Ok(_), Ok(_)) => {
+                Err(invalid_format("Both RNCRYPTOR_KEYS and RNCRYPTOR_PASSWORD are set."))
+            }
+            (Ok(keys), Err(_)) => Ok(Secret::Keys(keys.trim().parse()?)),
+            (Err(_), Ok(password)) => Ok(Secret::Password(password)),
+            (Err(_), Err(_)) => {
+                Err(io::Error::new(io::ErrorKind::NotFound,
+                                   "Neither RNCRYPTOR_KEYS nor RNCRYPTOR_PASSWORD is set.")
+                    .into())
+            }
+        }
+    }
+
+    pub fn credentials(&self) -> Credentials<'_> {
+        match *self {
+            Secret::Password(ref password) => Credentials::Password(password),
+            Secret::Keys(ref keys) => keys.credentials(),
+        }
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            Secret::Password(_) => f.write_str("Password(<redacted>)"),
+            Secret::Keys(_) => f.write_str("Keys(<redacted>)"),
+        }
+    }
+}
+
+/// Whether `value` looks like `ENC[...]`.
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX) && value.ends_with(SUFFIX) && value.len() > PREFIX.len()
+}
+
+/// Encrypts `value` as `ENC[...]`, drawing the salts and the `IV` from the given random number
+/// generator.
+pub fn encrypt_value_with_rng<R: RngCore + CryptoRng>(credentials: &Credentials,
+                                                      value: &str,
+                                                      rng: &mut R)
+                                                      -> Result<String> {
+    let message = Encryptor::from_credentials_with_rng(credentials, rng)?
+        .encrypt(value.as_bytes())?;
+    Ok(format!("{}{}{}", PREFIX, STANDARD.encode(message), SUFFIX))
+}
+
+/// Encrypts `value` as `ENC[...]`.
+pub fn encrypt_value(credentials: &Credentials, value: &str) -> Result<String> {
+    encrypt_value_with_rng(credentials, value, &mut OsRng)
+}
+
+/// Decrypts an `ENC[...]` value.
+pub fn decrypt_value(credentials: &Credentials, value: &str) -> Result<String> {
+    if !is_encrypted(value) {
+        return Err(invalid_format("Not an ENC[...] value."));
+    }
+    let message = STANDARD.decode(&value[PREFIX.len()..value.len() - SUFFIX.len()])
+        .map_err(|_| invalid_format("Invalid base64."))?;
+    let plain_text = Decryptor::from_credentials(credentials, &message)?.decrypt(&message)?;
+    String::from_utf8(plain_text).map_err(|_| invalid_format("The value isn't valid UTF-8."))
+}
+
+fn key_not_found(path: &str) -> Error {
+    invalid_format(&format!("No value at '{}'.", path))
+}
+
+fn not_a_scalar(path: &str) -> Error {
+    invalid_format(&format!("The value at '{}' can't be encrypted in place.", path))
+}
+
+#[cfg(any(feature = "json", feature = "yaml"))]
+fn join(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+/// Replaces the given spans of `text`, which mustn't overlap.
+#[cfg(any(feature = "json", feature = "yaml"))]
+fn splice(text: &str, mut replacements: Vec<(Range<usize>, String)>) -> String {
+    replacements.sort_by_key(|r| r.0.start);
+    let mut spliced = String::with_capacity(text.len());
+    let mut position = 0;
+    for (span, replacement) in replacements {
+        spliced.push_str(&text[position..span.start]);
+        spliced.push_str(&replacement);
+        position = span.end;
+    }
+    spliced.push_str(&text[position..]);
+    spliced
+}
+
+/// Decrypts every `ENC[...]` string of `value`, returning how many were decrypted.
+#[cfg(feature = "json")]
+pub fn decrypt_json(credentials: &Credentials, value: &mut serde_json::Value) -> Result<usize> {
+    use self::serde_json::Value;
+    match *value {
+        Value::String(ref mut s) if is_encrypted(s) => {
+            *s = decrypt_value(credentials, s)?;
+            Ok(1)
+        }
+        Value::Array(ref mut values) => {
+            values.iter_mut().map(|v| decrypt_json(credentials, v)).sum()
+        }
+        Value::Object(ref mut values) => {
+            values.values_mut().map(|v| decrypt_json(credentials, v)).sum()
+        }
+        _ => Ok(0),
+    }
+}
+
+/// Finds the spans of the scalars of a JSON document, by the dotted paths of their keys.
+#[cfg(feature = "json")]
+struct JsonScanner<'a> {
+    text: &'a [u8],
+    position: usize,
+    scalars: Vec<(String, Range<usize>)>,
+}
+
+#[cfg(feature = "json")]
+impl<'a> JsonScanner<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.position < self.text.len() && self.text[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn string(&mut self) -> Range<usize> {
+        let start = self.position;
+        self.position += 1;
+        while self.text[self.position] != b'"' {
+            self.position += if self.text[self.position] == b'\\' { 2 } else { 1 };
+        }
+        self.position += 1;
+        start..self.position
+    }
+
+    /// Scans the value at the current position; `path` is `None` inside arrays.
+    fn value(&mut self, path: Option<&str>) {
+        self.skip_whitespace();
+        match self.text[self.position] {
+            b'{' => {
+                self.position += 1;
+                loop {
+                    self.skip_whitespace();
+                    if self.text[self.position] == b'}' {
+                        break;
+                    }
+                    let key = self.string();
+                    let key: String = serde_json::from_slice(&self.text[key]).unwrap_or_default();
+                    self.skip_whitespace();
+                    self.position += 1; // ':'
+                    let child = path.map(|p| join(p, &key));
+                    self.value(child.as_deref());
+                    self.skip_whitespace();
+                    if self.text[self.position] == b',' {
+                        self.position += 1;
+                    }
+                }
+                self.position += 1;
+            }
+            b'[' => {
+                self.position += 1;
+                loop {
+                    self.skip_whitespace();
+                    if self.text[self.position] == b']' {
+                        break;
+                    }
+                    self.value(None);
+                    self.skip_whitespace();
+                    if self.text[self.position] == b',' {
+                        self.position += 1;
+                    }
+                }
+                self.position += 1;
+            }
+            b'"' => {
+                let span = self.string();
+                if let Some(path) = path {
+                    self.scalars.push((path.to_owned(), span));
+                }
+            }
+            _ => {
+                let start = self.position;
+                while self.position < self.text.len() &&
+                      !b",}] \t\r\n".contains(&self.text[self.position]) {
+                    self.position += 1;
+                }
+                if let Some(path) = path {
+                    self.scalars.push((path.to_owned(), start..self.position));
+                }
+            }
+        }
+    }
+}
+
+/// Encrypts the values at the dotted `paths` of a JSON document, leaving the rest of the text
+/// as it is.
+#[cfg(feature = "json")]
+pub fn encrypt_json_keys(credentials: &Credentials, text: &str, paths: &[&str]) -> Result<String> {
+    use self::serde_json::Value;
+    serde_json::from_str::<Value>(text).map_err(|_| invalid_format("Invalid JSON."))?;
+
+    let mut scanner = JsonScanner {
+        text: text.as_bytes(),
+        position: 0,
+        scalars: Vec::new(),
+    };
+    scanner.value(Some(""));
+
+    let mut replacements = Vec::with_capacity(paths.len());
+    for (i, path) in paths.iter().enumerate() {
+        if paths[..i].contains(path) {
+            continue;
+        }
+        let span = scanner.scalars
+            .iter()
+            .find(|s| s.0 == *path)
+            .map(|s| s.1.clone())
+            .ok_or_else(|| key_not_found(path))?;
+        let value = match serde_json::from_str::<Value>(&text[span.clone()]) {
+            Ok(Value::String(s)) => s,
+            Ok(Value::Number(n)) => n.to_string(),
+            Ok(Value::Bool(b)) => b.to_string(),
+            _ => return Err(not_a_scalar(path)),
+        };
+        if !is_encrypted(&value) {
+            let encrypted = encrypt_value(credentials, &value)?;
+            replacements.push((span, Value::String(encrypted).to_string()));
+        }
+    }
+    Ok(splice(text, replacements))
+}
+
+/// Decrypts every `ENC[...]` string of `document`, returning how many were decrypted. Their
+/// decorations (comments, whitespace) are kept.
+#[cfg(feature = "toml")]
+pub fn decrypt_toml(credentials: &Credentials,
+                    document: &mut toml_edit::DocumentMut)
+                    -> Result<usize> {
+    decrypt_toml_item(credentials, document.as_item_mut())
+}
+
+#[cfg(feature = "toml")]
+fn decrypt_toml_item(credentials: &Credentials, item: &mut toml_edit::Item) -> Result<usize> {
+    use self::toml_edit::Item;
+    match *item {
+        Item::Value(ref mut value) => decrypt_toml_value(credentials, value),
+        Item::Table(ref mut table) => {
+            table.iter_mut().map(|(_, item)| decrypt_toml_item(credentials, item)).sum()
+        }
+        Item::ArrayOfTables(ref mut tables) => {
+            let mut count = 0;
+            for table in tables.iter_mut() {
+                for (_, item) in table.iter_mut() {
+                    count += decrypt_toml_item(credentials, item)?;
+                }
+            }
+            Ok(count)
+        }
+        Item::None => Ok(0),
+    }
+}
+
+#[cfg(feature = "toml")]
+fn decrypt_toml_value(credentials: &Credentials, value: &mut toml_edit::Value) -> Result<usize> {
+    use self::toml_edit::Value;
+    match *value {
+        Value::String(ref s) if is_encrypted(s.value()) => {
+            let decrypted = decrypt_value(credentials, s.value())?;
+            replace_toml_value(value, decrypted);
+            Ok(1)
+        }
+        Value::Array(ref mut values) => {
+            values.iter_mut().map(|v| decrypt_toml_value(credentials, v)).sum()
+        }
+        Value::InlineTable(ref mut table) => {
+            table.iter_mut().map(|(_, v)| decrypt_toml_value(credentials, v)).sum()
+        }
+        _ => Ok(0),
+    }
+}
+
+/// Replaces `value` with the string `new_value`, keeping its decorations.
+#[cfg(feature = "toml")]
+fn replace_toml_value(value: &mut toml_edit::Value, new_value: String) {
+    let decor = value.decor().clone();
+    *value = toml_edit::Value::from(new_value);
+    *value.decor_mut() = decor;
+}
+
+/// Encrypts the values at the dotted `paths` of a TOML document, leaving the rest of the text
+/// (including the comments) as it is.
+#[cfg(feature = "toml")]
+pub fn encrypt_toml_keys(credentials: &Credentials, text: &str, paths: &[&str]) -> Result<String> {
+    use self::toml_edit::{DocumentMut, Item, Value};
+    let mut document = text.parse::<DocumentMut>().map_err(|_| invalid_format("Invalid TOML."))?;
+
+    for path in paths {
+        let mut item = document.as_item_mut();
+        for key in path.split('.') {
+            item = item.as_table_like_mut()
+                .and_then(|table| table.get_mut(key))
+                .ok_or_else(|| key_not_found(path))?;
+        }
+        let value = match *item {
+            Item::Value(ref mut value) => value,
+            _ => return Err(not_a_scalar(path)),
+        };
+        let plain = match *value {
+            Value::String(ref s) => s.value().clone(),
+            Value::Integer(ref i) => i.value().to_string(),
+            Value::Float(ref f) => f.value().to_string(),
+            Value::Boolean(ref b) => b.value().to_string(),
+            _ => return Err(not_a_scalar(path)),
+        };
+        if !is_encrypted(&plain) {
+            let encrypted = encrypt_value(credentials, &plain)?;
+            replace_toml_value(value, encrypted);
+        }
+    }
+    Ok(document.to_string())
+}
+
+/// Decrypts every `ENC[...]` string of `value`, returning how many were decrypted.
+#[cfg(feature = "yaml")]
+pub fn decrypt_yaml(credentials: &Credentials, value: &mut serde_yaml::Value) -> Result<usize> {
+    use self::serde_yaml::Value;
+    match *value {
+        Value::String(ref mut s) if is_encrypted(s) => {
+            *s = decrypt_value(credentials, s)?;
+            Ok(1)
+        }
+        Value::Sequence(ref mut values) => {
+            values.iter_mut().map(|v| decrypt_yaml(credentials, v)).sum()
+        }
+        Value::Mapping(ref mut values) => {
+            values.values_mut().map(|v| decrypt_yaml(credentials, v)).sum()
+        }
+        Value::Tagged(ref mut tagged) => decrypt_yaml(credentials, &mut tagged.value),
+        _ => Ok(0),
+    }
+}
+
+/// The end of the scalar starting `value`: after its closing quote, or before a comment.
+#[cfg(feature = "yaml")]
+fn yaml_scalar_len(value: &str) -> usize {
+    let bytes = value.as_bytes();
+    match bytes.first() {
+        Some(&b'"') => {
+            let mut position = 1;
+            while position < bytes.len() && bytes[position] != b'"' {
+                position += if bytes[position] == b'\\' { 2 } else { 1 };
+            }
+            (position + 1).min(bytes.len())
+        }
+        Some(&b'\'') => {
+            let mut position = 1;
+            while position < bytes.len() {
+                if bytes[position] == b'\'' {
+                    if bytes.get(position + 1) == Some(&b'\'') {
+                        position += 2;
+                        continue;
+                    }
+                    break;
+                }
+                position += 1;
+            }
+            (position + 1).min(bytes.len())
+        }
+        _ => value.find(" #").unwrap_or(value.len()).min(value.trim_end().len()),
+    }
+}
+
+/// Finds the spans of the single-line scalars of the block mappings of a YAML document, by the
+/// dotted paths of their keys. The lines of block scalars (`|` and `>`) are skipped.
+#[cfg(feature = "yaml")]
+fn yaml_scalars(text: &str) -> Vec<(String, Range<usize>)> {
+    use self::serde_yaml::Value;
+    let mut scalars = Vec::new();
+    // The indentation and key of the enclosing mappings, `None` for sequence items.
+    let mut parents: Vec<(usize, Option<String>)> = Vec::new();
+    // The indentation of the line starting the block scalar being skipped, if any.
+    let mut block_scalar = None;
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let start = line_start;
+        line_start += line.len();
+        let content = line.trim_start_matches(' ');
+        let indent = line.len() - content.len();
+        let content = content.trim_end_matches(['\r', '\n']);
+        if let Some(block_indent) = block_scalar {
+            if content.is_empty() || indent > block_indent {
+                continue;
+            }
+            block_scalar = None;
+        }
+        if content.is_empty() || content.starts_with('#') || content.starts_with("---") {
+            continue;
+        }
+        while parents.last().is_some_and(|p| p.0 >= indent) {
+            parents.pop();
+        }
+        if content == "-" || content.starts_with("- ") {
+            parents.push((indent, None));
+            if content[1..].trim_start().starts_with(['|', '>']) {
+                block_scalar = Some(indent);
+            }
+            continue;
+        }
+
+        let colon = match content.find(": ").or_else(|| content.strip_suffix(':').map(|c| c.len())) {
+            Some(colon) => colon,
+            None => continue,
+        };
+        let key = match serde_yaml::from_str::<Value>(&content[..colon]) {
+            Ok(Value::String(key)) => key,
+            _ => content[..colon].to_owned(),
+        };
+        let value = content[colon + 1..].trim_start();
+        if value.is_empty() || value.starts_with('#') {
+            parents.push((indent, Some(key)));
+            continue;
+        }
+        if value.starts_with(['|', '>']) {
+            block_scalar = Some(indent);
+        }
+        if parents.iter().all(|p| p.1.is_some()) {
+            let path = parents.iter()
+                .filter_map(|p| p.1.as_ref())
+                .fold(String::new(), |path, key| join(&path, key));
+            let value_start = start + indent + (content.len() - value.len());
+            scalars.push((join(&path, &key), value_start..value_start + yaml_scalar_len(value)));
+        }
+    }
+    scalars
+}
+
+/// Encrypts the values at the dotted `paths` of a YAML document, leaving the rest of the text
+/// (including the comments) as it is. See the module documentation for the limitations.
+#[cfg(feature = "yaml")]
+pub fn encrypt_yaml_keys(credentials: &Credentials, text: &str, paths: &[&str]) -> Result<String> {
+    use self::serde_yaml::Value;
+    let document = serde_yaml::from_str::<Value>(text)
+        .map_err(|_| invalid_format("Invalid YAML."))?;
+
+    let scalars = yaml_scalars(text);
+    let mut replacements = Vec::with_capacity(paths.len());
+    for (i, path) in paths.iter().enumerate() {
+        if paths[..i].contains(path) {
+            continue;
+        }
+        // The scan of the text is only a heuristic, so its findings are checked against the
+        // parsed document.
+        let parsed = path.split('.')
+            .try_fold(&document, |value, key| value.as_mapping()?.get(key))
+            .ok_or_else(|| key_not_found(path))?;
+        let span = scalars.iter()
+            .find(|s| s.0 == *path)
+            .map(|s| s.1.clone())
+            .ok_or_else(|| key_not_found(path))?;
+        let scalar = &text[span.clone()];
+        if scalar.starts_with(['|', '>', '&', '*', '!', '[', '{']) {
+            return Err(not_a_scalar(path));
+        }
+        let value = match serde_yaml::from_str::<Value>(scalar) {
+            Ok(ref value) if value != parsed => return Err(not_a_scalar(path)),
+            Ok(Value::String(s)) => s,
+            Ok(Value::Number(n)) => n.to_string(),
+            Ok(Value::Bool(b)) => b.to_string(),
+            _ => return Err(not_a_scalar(path)),
+        };
+        if !is_encrypted(&value) {
+            replacements.push((span, encrypt_value(credentials, &value)?));
+        }
+    }
+    Ok(splice(text, replacements))
+}
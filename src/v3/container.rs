@@ -0,0 +1,413 @@
+//! A seekable container, splitting a stream into fixed-size chunks which can be decrypted
+//! independently.
+//!
+//! A plain v3 `Message` has to be authenticated in full before decrypting any of it, which rules
+//! out random access. A container is instead laid out as:
+//!
+//! ```text
+//! header | chunk 0 | chunk 1 | ... | chunk n-1 | index
+//! ```
+//!
+//! * The header is `"RNCC" | version (1) | options (1) | chunk size (u32, BE) | id (16)`,
+//!   followed by the encryption and HMAC salts (8 bytes each) when the container is
+//!   password-based. In that case the keys are derived only once, for the whole container.
+//! * Every chunk is a v3 key-based `Message`, whose plain text is prefixed with the container id,
+//!   a kind byte (`0`) and its sequence number (u64, BE), so chunks can't be reordered or moved
+//!   across containers. All chunks but the last one hold exactly `chunk size` bytes.
+//! * The index is a last key-based `Message`, prefixed with the id, a kind byte (`1`) and the
+//!   number of chunks, holding the total plain text length and a copy of the header. It
+//!   authenticates the header, and catches truncation.
+//!
+//! **This is not part of the RNCryptor spec**: other implementations can't read containers,
+//! though each chunk is a regular v3 message.
+
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::mem;
+use std::vec::Vec;
+use std::borrow::ToOwned;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::decryptor::Decryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::kdf;
+use v3::rng::{RngCore, CryptoRng, OsRng};
+
+/// The magic bytes starting every container.
+pub const MAGIC: &[u8; 4] = b"RNCC";
+/// The version of the container format.
+pub const VERSION: u8 = 1;
+/// The chunk size used by `ContainerWriter::new`.
+pub const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
+
+const OPTION_PASSWORD: u8 = 1;
+const FIXED_LEN: usize = 10;
+const ID_LEN: usize = 16;
+const PREFIX_LEN: usize = ID_LEN + 1 + 8;
+const KIND_CHUNK: u8 = 0;
+const KIND_INDEX: u8 = 1;
+
+fn header_len(options: u8) -> usize {
+    let salts_len = if options & OPTION_PASSWORD != 0 { 16 } else { 0 };
+    FIXED_LEN + ID_LEN + salts_len
+}
+
+/// The length of the key-based `Message` holding `plain_text_len` bytes.
+fn message_len(plain_text_len: u64) -> u64 {
+    18 + (plain_text_len / 16 + 1) * 16 + 32
+}
+
+fn prefix(id: &[u8], kind: u8, sequence: u64) -> Vec<u8> {
+    let mut prefix = Vec::with_capacity(PREFIX_LEN);
+    prefix.extend_from_slice(id);
+    prefix.push(kind);
+    prefix.extend_from_slice(&sequence.to_be_bytes());
+    prefix
+}
+
+fn invalid_format(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidFormat, message.to_owned())
+}
+
+fn keys(credentials: &Credentials,
+        options: u8,
+        salts: &[u8])
+        -> Result<(EncryptionKey, HMACKey)> {
+    match (credentials, options & OPTION_PASSWORD != 0) {
+        (&Credentials::Password(password), true) => {
+            if password.is_empty() {
+                return Err(Error::new(ErrorKind::WrongInputSize(0),
+                                      "Password length cannot be <= 0.".to_owned()));
+            }
+            Ok(kdf::derive_keys(password.as_bytes(),
+                                &Salt(salts[..8].to_vec()),
+                                &Salt(salts[8..16].to_vec())))
+        }
+        (Credentials::Keys(ek, hk), false) => Ok((ek.clone(), hk.clone())),
+        (&Credentials::Password(_), false) => {
+            Err(invalid_format("The container is key-based, not password-based."))
+        }
+        (&Credentials::Keys(..), true) => {
+            Err(invalid_format("The container is password-based, not key-based."))
+        }
+    }
+}
+
+/// A `Write` adapter which encrypts everything written to it into a container.
+///
+/// Nothing but the header is written until a whole chunk is buffered, and the last chunk and the
+/// index only when calling `finish`: a container which wasn't finished can't be opened.
+pub struct ContainerWriter<W: Write, G: RngCore + CryptoRng = OsRng> {
+    inner: W,
+    rng: G,
+    encryption_key: EncryptionKey,
+    hmac_key: HMACKey,
+    header: Vec<u8>,
+    chunk_size: usize,
+    pending: Vec<u8>,
+    chunks: u64,
+    len: u64,
+}
+
+impl<W: Write> ContainerWriter<W> {
+    /// Writes the header of a new container, split in chunks of `DEFAULT_CHUNK_SIZE` bytes,
+    /// to `inner`.
+    pub fn new(credentials: &Credentials, inner: W) -> Result<ContainerWriter<W>> {
+        ContainerWriter::with_chunk_size(credentials, DEFAULT_CHUNK_SIZE, inner, OsRng)
+    }
+}
+
+impl<W: Write, G: RngCore + CryptoRng> ContainerWriter<W, G> {
+    /// Writes the header of a new container, split in chunks of `chunk_size` bytes, to `inner`.
+    /// The id, the salts and the `IV` of every chunk are drawn from `rng`.
+    pub fn with_chunk_size(credentials: &Credentials,
+                           chunk_size: u32,
+                           mut inner: W,
+                           mut rng: G)
+                           -> Result<ContainerWriter<W, G>> {
+        if chunk_size == 0 {
+            return Err(Error::new(ErrorKind::WrongInputSize(0),
+                                  "Chunk size cannot be 0.".to_owned()));
+        }
+
+        let options = match *credentials {
+            Credentials::Password(_) => OPTION_PASSWORD,
+            Credentials::Keys(..) => 0,
+        };
+        let mut header = Vec::with_capacity(header_len(options));
+        header.extend_from_slice(MAGIC);
+        header.push(VERSION);
+        header.push(options);
+        header.extend_from_slice(&chunk_size.to_be_bytes());
+        let mut id = [0; ID_LEN];
+        rng.try_fill_bytes(&mut id).map_err(ErrorKind::IVGenerationFailed)?;
+        header.extend_from_slice(&id);
+        if options & OPTION_PASSWORD != 0 {
+            header.extend_from_slice(Salt::new_with_rng(&mut rng)?.as_slice());
+            header.extend_from_slice(Salt::new_with_rng(&mut rng)?.as_slice());
+        }
+
+        let (encryption_key, hmac_key) = keys(credentials, options, &header[FIXED_LEN + ID_LEN..])?;
+        inner.write_all(&header)?;
+
+        Ok(ContainerWriter {
+            inner,
+            rng,
+            encryption_key,
+            hmac_key,
+            header,
+            chunk_size: chunk_size as usize,
+            pending: Vec::with_capacity(chunk_size as usize),
+            chunks: 0,
+            len: 0,
+        })
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    fn id(&self) -> &[u8] {
+        &self.header[FIXED_LEN..FIXED_LEN + ID_LEN]
+    }
+
+    fn write_message(&mut self, kind: u8, sequence: u64, data: &[u8]) -> Result<()> {
+        let mut plain_text = prefix(self.id(), kind, sequence);
+        plain_text.extend_from_slice(data);
+        let encryptor = Encryptor::from_keys_with_rng(self.encryption_key.clone(),
+                                                      self.hmac_key.clone(),
+                                                      &mut self.rng)?;
+        let message = encryptor.encrypt(&plain_text)?;
+        self.inner.write_all(&message)?;
+        Ok(())
+    }
+
+    /// Encrypts and writes the pending chunk, which is only cleared once written, so that a
+    /// failed write can be retried.
+    fn write_chunk(&mut self) -> Result<()> {
+        let data = mem::take(&mut self.pending);
+        let written = self.write_message(KIND_CHUNK, self.chunks, &data);
+        self.pending = data;
+        written?;
+        self.chunks += 1;
+        self.len += self.pending.len() as u64;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Encrypts the last chunk, then writes the index, returning the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        if !self.pending.is_empty() {
+            self.write_chunk()?;
+        }
+
+        let mut index = self.len.to_be_bytes().to_vec();
+        index.extend_from_slice(&self.header);
+        self.write_message(KIND_INDEX, self.chunks, &index)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write, G: RngCore + CryptoRng> Write for ContainerWriter<W, G> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A full chunk is only written once more data comes (or by `finish`), so that nothing
+        // of `buf` is taken if writing it fails.
+        if self.pending.len() == self.chunk_size {
+            self.write_chunk()?;
+        }
+        let len = buf.len().min(self.chunk_size - self.pending.len());
+        self.pending.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A `Read + Seek` view over the plain text of a container, which only reads and decrypts the
+/// chunks actually touched.
+///
+/// Every chunk is authenticated before any of its bytes are returned; failures are reported as
+/// `io::ErrorKind::InvalidData` errors.
+pub struct ContainerReader<R> {
+    inner: R,
+    start: u64,
+    encryption_key: EncryptionKey,
+    hmac_key: HMACKey,
+    id: Vec<u8>,
+    header_len: u64,
+    chunk_size: u64,
+    chunks: u64,
+    len: u64,
+    position: u64,
+    chunk: Option<(u64, Vec<u8>)>,
+}
+
+impl<R: Read + Seek> ContainerReader<R> {
+    /// Opens the container starting at the current position of `inner`, which must extend up to
+    /// the end of the stream. Only the header and the index are read and validated.
+    pub fn open(credentials: &Credentials, mut inner: R) -> Result<ContainerReader<R>> {
+        let start = inner.stream_position()?;
+        let end = inner.seek(SeekFrom::End(0))?;
+        inner.seek(SeekFrom::Start(start))?;
+
+        let mut fixed = [0; FIXED_LEN];
+        if end - start < fixed.len() as u64 {
+            return Err(Error::new(ErrorKind::NotEnoughInput((end - start) as usize),
+                                  "Not enough input for a container.".to_owned()));
+        }
+        inner.read_exact(&mut fixed)?;
+        if &fixed[..4] != MAGIC {
+            return Err(invalid_format("Not a container."));
+        }
+        if fixed[4] != VERSION {
+            return Err(invalid_format("Unsupported container version."));
+        }
+
+        let options = fixed[5];
+        let header_len = header_len(options);
+        let index_len = message_len((PREFIX_LEN + 8 + header_len) as u64);
+        if end - start < header_len as u64 + index_len {
+            return Err(Error::new(ErrorKind::NotEnoughInput((end - start) as usize),
+                                  "Not enough input for a container.".to_owned()));
+        }
+        let mut header = fixed.to_vec();
+        header.resize(header_len, 0);
+        inner.read_exact(&mut header[FIXED_LEN..])?;
+        let id = header[FIXED_LEN..FIXED_LEN + ID_LEN].to_vec();
+        let (encryption_key, hmac_key) = keys(credentials, options, &header[FIXED_LEN + ID_LEN..])?;
+
+        inner.seek(SeekFrom::Start(end - index_len))?;
+        let mut message = vec![0; index_len as usize];
+        inner.read_exact(&mut message)?;
+        let index = Decryptor::from_keys(encryption_key.clone(), hmac_key.clone(), &message)?
+            .decrypt(&message)?;
+        if index.len() != PREFIX_LEN + 8 + header_len || index[..ID_LEN] != id[..] ||
+           index[ID_LEN] != KIND_INDEX || index[PREFIX_LEN + 8..] != header[..] {
+            return Err(invalid_format("Invalid container index."));
+        }
+
+        let mut word = [0; 8];
+        word.copy_from_slice(&index[ID_LEN + 1..PREFIX_LEN]);
+        let chunks = u64::from_be_bytes(word);
+        word.copy_from_slice(&index[PREFIX_LEN..PREFIX_LEN + 8]);
+        let len = u64::from_be_bytes(word);
+
+        let chunk_size = u32::from_be_bytes([header[6], header[7], header[8], header[9]]) as u64;
+        if chunk_size == 0 {
+            return Err(invalid_format("Invalid chunk size."));
+        }
+        let chunks_len = if chunks != len.div_ceil(chunk_size) {
+            None
+        } else if chunks == 0 {
+            Some(0)
+        } else {
+            let last_len = len - (chunks - 1) * chunk_size;
+            (chunks - 1)
+                .checked_mul(message_len(PREFIX_LEN as u64 + chunk_size))
+                .and_then(|n| n.checked_add(message_len(PREFIX_LEN as u64 + last_len)))
+        };
+        let actual_len = end - start - header_len as u64 - index_len;
+        if chunks_len != Some(actual_len) {
+            return Err(invalid_format("The container was truncated or extended."));
+        }
+
+        Ok(ContainerReader {
+            inner,
+            start,
+            encryption_key,
+            hmac_key,
+            id,
+            header_len: header_len as u64,
+            chunk_size,
+            chunks,
+            len,
+            position: 0,
+            chunk: None,
+        })
+    }
+
+    /// The length of the plain text.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the plain text is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Reads and decrypts the chunk `n`, unless it's already cached.
+    fn load_chunk(&mut self, n: u64) -> Result<()> {
+        if let Some((cached, _)) = self.chunk {
+            if cached == n {
+                return Ok(());
+            }
+        }
+
+        let data_len = if n + 1 == self.chunks {
+            self.len - n * self.chunk_size
+        } else {
+            self.chunk_size
+        };
+        let full_len = message_len(PREFIX_LEN as u64 + self.chunk_size);
+        self.inner.seek(SeekFrom::Start(self.start + self.header_len + n * full_len))?;
+        let mut message = vec![0; message_len(PREFIX_LEN as u64 + data_len) as usize];
+        self.inner.read_exact(&mut message)?;
+
+        let mut plain_text = Decryptor::from_keys(self.encryption_key.clone(),
+                                                  self.hmac_key.clone(),
+                                                  &message)?
+            .decrypt(&message)?;
+        if plain_text.len() as u64 != PREFIX_LEN as u64 + data_len ||
+           plain_text[..PREFIX_LEN] != prefix(&self.id, KIND_CHUNK, n)[..] {
+            return Err(invalid_format("Chunk out of sequence."));
+        }
+        plain_text.drain(..PREFIX_LEN);
+        self.chunk = Some((n, plain_text));
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for ContainerReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let n = self.position / self.chunk_size;
+        self.load_chunk(n)?;
+        let offset = (self.position - n * self.chunk_size) as usize;
+        let chunk = match self.chunk {
+            Some((_, ref chunk)) => &chunk[offset..],
+            None => unreachable!(),
+        };
+        let len = chunk.len().min(buf.len());
+        buf[..len].copy_from_slice(&chunk[..len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for ContainerReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.len.checked_add_signed(n),
+            SeekFrom::Current(n) => self.position.checked_add_signed(n),
+        };
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => {
+                Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                   "invalid seek to a negative or overflowing position"))
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Convergent (deterministic) encryption, so that encrypted chunks can be deduplicated: the same
+//! plain text, under the same secrets, always gives the same v3 `Message`.
+//!
+//! Instead of random salts and `IV`, everything is drawn from a keyed hash of the plain text,
+//! `HMAC-SHA256(convergence key, "rncryptor-convergent:v1" | plain text)`: its 32 bytes are the
+//! encryption salt, the HMAC salt (8 bytes each, only used with passwords) and the `IV`. With
+//! `encrypt_with_derived_keys`, the keys themselves are also derived from the convergence key
+//! and this hash, and handed back to be stored alongside the reference to the chunk. The
+//! resulting messages are regular v3 messages, which any implementation can decrypt.
+//!
+//! # Caveats
+//!
+//! **Determinism leaks equality**: anyone seeing two messages learns whether their plain texts
+//! are the same, which is the whole point for deduplication, but may not be acceptable
+//! elsewhere. Moreover, whoever holds the convergence key (and the credentials) can:
+//!
+//! * confirm that a message holds a given plain text, by encrypting it and comparing (the
+//!   "confirmation of a file" attack);
+//! * recover a plain text which is known but for a few low-entropy fields (a PIN, a salary...)
+//!   by trying every value (the "learn the remaining information" attack).
+//!
+//! The convergence key is what confines these attacks to its holders: keep it as secret as the
+//! credentials, and use distinct ones for data which mustn't be cross-checked. Never use this
+//! mode for small or guessable plain texts.
+
+extern crate hmac;
+extern crate sha2;
+
+use alloc::borrow::ToOwned;
+
+use self::hmac::{Hmac, Mac};
+use self::sha2::Sha256;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::kdf::{hkdf_keys_with_salt, KEY_LEN};
+use v3::keys::KeyPair;
+
+/// The minimum length of a convergence key.
+pub const MIN_CONVERGENCE_KEY_LEN: usize = KEY_LEN;
+
+const HASH_DOMAIN: &[u8] = b"rncryptor-convergent:v1";
+const KEYS_INFO: &[u8] = b"rncryptor-convergent:v1 keys";
+
+/// The keyed hash of `plain_text`, which everything else is drawn from.
+fn convergence_hash(convergence_key: &[u8], plain_text: &PlainText) -> Result<[u8; 32]> {
+    if convergence_key.len() < MIN_CONVERGENCE_KEY_LEN {
+        return Err(Error::new(ErrorKind::WrongInputSize(convergence_key.len()),
+                              "The convergence key is too short.".to_owned()));
+    }
+
+    let mut hmac = Hmac::<Sha256>::new_from_slice(convergence_key).map_err(|_| {
+        Error::new(ErrorKind::HMACGenerationFailed,
+                   "HMAC Generation failed.".to_owned())
+    })?;
+    hmac.update(HASH_DOMAIN);
+    hmac.update(plain_text);
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&hmac.finalize().into_bytes());
+    Ok(hash)
+}
+
+/// Deterministically encrypts `plain_text` with `credentials`, the salts (for passwords) and the
+/// `IV` being drawn from its hash under `convergence_key`. See the caveats above.
+pub fn encrypt(credentials: &Credentials,
+               convergence_key: &[u8],
+               plain_text: &PlainText)
+               -> Result<Message> {
+    let hash = convergence_hash(convergence_key, plain_text)?;
+    let iv = IV::from(hash[16..].to_vec());
+    let encryptor = match *credentials {
+        Credentials::Password(password) => {
+            Encryptor::from_password(password,
+                                     Salt(hash[..8].to_vec()),
+                                     Salt(hash[8..16].to_vec()),
+                                     iv)?
+        }
+        Credentials::Keys(ref ek, ref hk) => Encryptor::from_keys(ek.clone(), hk.clone(), iv)?,
+    };
+    encryptor.encrypt(plain_text)
+}
+
+/// The `KeyPair` which `encrypt_with_derived_keys` would use for `plain_text`.
+pub fn derive_keys(convergence_key: &[u8], plain_text: &PlainText) -> Result<KeyPair> {
+    let hash = convergence_hash(convergence_key, plain_text)?;
+    let (encryption_key, hmac_key) = hkdf_keys_with_salt(convergence_key, &hash, KEYS_INFO)?;
+    KeyPair::new(encryption_key, hmac_key)
+}
+
+/// Deterministically encrypts `plain_text` with keys and an `IV` drawn from its hash under
+/// `convergence_key`, returning the keys (needed to decrypt, with the key-based API) along with
+/// the `Message`. See the caveats above.
+pub fn encrypt_with_derived_keys(convergence_key: &[u8],
+                                 plain_text: &PlainText)
+                                 -> Result<(KeyPair, Message)> {
+    let keys = derive_keys(convergence_key, plain_text)?;
+    let message = encrypt(&keys.credentials(), convergence_key, plain_text)?;
+    Ok((keys, message))
+}
@@ -1,18 +1,19 @@
-
-extern crate crypto;
+extern crate aes;
+extern crate cbc;
 
 use v3::types::*;
-use v3::errors::{Result, Error, ErrorKind};
-use self::crypto::aes;
-use self::crypto::blockmodes;
-use self::crypto::buffer::{WriteBuffer, ReadBuffer, RefReadBuffer, RefWriteBuffer, BufferResult};
+use v3::errors::{Result, Error, ErrorKind, SymmetricCipherError};
+use self::cbc::cipher::{BlockDecryptMut, KeyIvInit};
+use self::cbc::cipher::block_padding::Pkcs7;
+use alloc::borrow::ToOwned;
+
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
 
 /// A "Decryptor", which is nothing more than a data structure to keep around the RNCryptor context
 pub struct Decryptor {
     pub version: u8,
     pub options: u8,
-    encryption_salt: EncryptionSalt,
-    hmac_salt: HMACSalt,
+    header: Header,
     encryption_key: EncryptionKey,
     pub hmac_key: HMACKey,
     iv: IV,
@@ -38,70 +39,119 @@ impl Decryptor {
         Ok(Decryptor {
             version: version,
             options: options,
-            encryption_salt: encryption_salt,
+            header: Header(message[..34].to_vec()),
             encryption_key: encryption_key,
             hmac_key: hmac_key,
-            hmac_salt: hmac_salt,
             iv: iv,
         })
 
     }
 
-    fn plain_text(&self, cipher_text: &[u8]) -> Result<Message> {
-        let iv  = self.iv.to_vec();
-        let key = self.encryption_key.to_vec();
-        let mut decryptor = aes::cbc_decryptor(
-            aes::KeySize::KeySize256,
-            key,
-            iv,
-            blockmodes::PkcsPadding);
-
-        // Usage taken from: https://github.com/DaGenix/rust-crypto/blob/master/examples/symmetriccipher.rs
-        let mut final_result = Vec::<u8>::new();
-        let mut buffer = [0; 4096];
-        let mut write_buffer = RefWriteBuffer::new(&mut buffer);
-        let mut read_buffer  = RefReadBuffer::new(cipher_text);
-
-        loop {
-            let result = try!(decryptor.decrypt(&mut read_buffer, &mut write_buffer, true)
-                              .map_err(ErrorKind::DecryptionFailed));
-            final_result.extend(write_buffer.take_read_buffer().take_remaining().iter().map(|&i| i));
-
-            match result {
-                BufferResult::BufferUnderflow => break,
-                BufferResult::BufferOverflow => { }
-            }
+    /// Builds a "Decryptor" out of an `EncryptionKey`, an `HMACKey` and a message (to decrypt)
+    /// produced by the key-based API.
+    pub fn from_keys(ek: EncryptionKey, hk: HMACKey, message: &[u8]) -> Result<Decryptor> {
+        let msg_len = message.len();
+        if msg_len < 66 {
+            return Err(Error::new(ErrorKind::NotEnoughInput(msg_len),
+                                  "Decryption failed, not enough input.".to_owned()));
         }
 
-        Ok(final_result)
+        Ok(Decryptor {
+            version: message[0],
+            options: message[1],
+            header: Header(message[..18].to_vec()),
+            encryption_key: ek,
+            hmac_key: hk,
+            iv: IV::from(message[2..18].to_vec()),
+        })
+    }
 
+    /// Builds a "Decryptor" out of `Credentials` and a message (to decrypt).
+    pub fn from_credentials(credentials: &Credentials, message: &[u8]) -> Result<Decryptor> {
+        match *credentials {
+            Credentials::Password(password) => Decryptor::from(password, message),
+            Credentials::Keys(ref ek, ref hk) => {
+                Decryptor::from_keys(ek.clone(), hk.clone(), message)
+            }
+        }
     }
 
-    /// Decrypts a `cipher_text`, returning a `Message` or an `Error`.
-    pub fn decrypt(&self, cipher_text: &[u8]) -> Result<Message> {
+    fn plain_text_into(&self, cipher_text: &[u8], out: &mut [u8]) -> Result<usize> {
+        if cipher_text.is_empty() || !cipher_text.len().is_multiple_of(16) {
+            return Err(ErrorKind::DecryptionFailed(SymmetricCipherError::InvalidLength).into());
+        }
 
-        let mut header: Vec<u8> = Vec::new();
-        header.push(3);
-        header.push(1);
-        header.extend(self.encryption_salt.as_slice().iter());
-        header.extend(self.hmac_salt.as_slice().iter());
-        header.extend(self.iv.as_slice().iter());
+        let decryptor = Aes256CbcDec::new_from_slices(self.encryption_key.to_vec(),
+                                                      self.iv.as_slice())
+            .map_err(|_| ErrorKind::DecryptionFailed(SymmetricCipherError::InvalidLength))?;
 
-        //TODO: Do not depend from drain, as this is O(n).
-        let mut cipher_text_vec = Vec::from(&cipher_text[34..]);
-        let hmac_position = cipher_text_vec.len() - 32;
-        let hmac0 = cipher_text_vec.drain(hmac_position..).collect();
+        let buffer = &mut out[..cipher_text.len()];
+        buffer.copy_from_slice(cipher_text);
+        let plain_text = decryptor.decrypt_padded_mut::<Pkcs7>(buffer)
+            .map_err(|_| ErrorKind::DecryptionFailed(SymmetricCipherError::InvalidPadding))?;
+        Ok(plain_text.len())
+    }
 
-        let encrypted = cipher_text_vec.as_slice();
+    /// Decrypts a `cipher_text` directly into `out`, returning the length of the plain text.
+    /// As the padding is only known after decryption, `out` needs to be at least as long as
+    /// the encrypted payload (the `cipher_text` minus its header and HMAC), otherwise
+    /// `ErrorKind::BufferTooSmall` is returned.
+    pub fn decrypt_into(&self, cipher_text: &[u8], out: &mut [u8]) -> Result<usize> {
+        self.decrypt_into_with(cipher_text, None, out)
+    }
 
-        let message = try!(self.plain_text(encrypted));
+    fn decrypt_into_with(&self,
+                         cipher_text: &[u8],
+                         associated_data: Option<&[u8]>,
+                         out: &mut [u8])
+                         -> Result<usize> {
+        let header = &self.header;
+        let header_len = header.0.len();
+        if cipher_text.len() < header_len + 32 {
+            return Err(Error::new(ErrorKind::NotEnoughInput(cipher_text.len()),
+                                  "Decryption failed, not enough input.".to_owned()));
+        }
 
-        let hmac = HMAC(hmac0);
-        let computed_hmac = try!(HMAC::new(&Header(header), cipher_text_vec.as_slice(), &self.hmac_key));
+        let hmac_position = cipher_text.len() - 32;
+        let encrypted = &cipher_text[header_len..hmac_position];
+        if out.len() < encrypted.len() {
+            return Err(Error::new(ErrorKind::BufferTooSmall(encrypted.len()),
+                                  "Output buffer too small.".to_owned()));
+        }
 
-        match hmac.is_equal_in_consistent_time_to(&computed_hmac) {
-            true  => Ok(message),
-            false => Err(Error::new(ErrorKind::HMACValidationFailed, "HMAC mismatch.".to_owned())),
+        let hmac = HMAC(cipher_text[hmac_position..].to_vec());
+        let computed_hmac = match associated_data {
+            None => HMAC::new(header, encrypted, &self.hmac_key)?,
+            Some(associated_data) => {
+                HMAC::with_associated_data(header, encrypted, associated_data, &self.hmac_key)?
+            }
+        };
+        if !hmac.is_equal_in_consistent_time_to(&computed_hmac) {
+            return Err(Error::new(ErrorKind::HMACValidationFailed, "HMAC mismatch.".to_owned()));
         }
+
+        self.plain_text_into(encrypted, out)
+    }
+
+    /// Decrypts a `cipher_text`, returning a `Message` or an `Error`.
+    pub fn decrypt(&self, cipher_text: &[u8]) -> Result<Message> {
+        let mut message = vec![0; cipher_text.len()];
+        let len = self.decrypt_into(cipher_text, &mut message)?;
+        message.truncate(len);
+        Ok(message)
+    }
+
+    /// Decrypts a `cipher_text` produced by `Encryptor::encrypt_with_associated_data`, failing
+    /// with `ErrorKind::HMACValidationFailed` unless `associated_data` is the same.
+    ///
+    /// **Not part of the v3 spec**, see `v3::aad`.
+    pub fn decrypt_with_associated_data(&self,
+                                        cipher_text: &[u8],
+                                        associated_data: &[u8])
+                                        -> Result<Message> {
+        let mut message = vec![0; cipher_text.len()];
+        let len = self.decrypt_into_with(cipher_text, Some(associated_data), &mut message)?;
+        message.truncate(len);
+        Ok(message)
     }
 }
@@ -1,10 +1,17 @@
-extern crate crypto;
+extern crate aes;
+extern crate cbc;
 
 use v3::types::*;
-use v3::errors::{Result, Error, ErrorKind};
-use self::crypto::buffer::{WriteBuffer, ReadBuffer, RefReadBuffer, RefWriteBuffer, BufferResult};
-use self::crypto::aes;
-use self::crypto::blockmodes;
+use v3::errors::{Result, Error, ErrorKind, SymmetricCipherError};
+use v3::rng::{RngCore, CryptoRng};
+#[cfg(feature = "std")]
+use v3::rng::OsRng;
+use self::cbc::cipher::{BlockEncryptMut, KeyIvInit};
+use self::cbc::cipher::block_padding::Pkcs7;
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
 
 #[derive(Clone)]
 pub struct Encryptor {
@@ -21,7 +28,7 @@ impl Encryptor {
                          iv: IV)
                          -> Result<Encryptor> {
 
-        if password.len() <= 0 {
+        if password.is_empty() {
             return Err(Error::new(ErrorKind::WrongInputSize(password.len()),
                                   "Password length cannot be <= 0.".to_owned()));
         }
@@ -41,6 +48,17 @@ impl Encryptor {
         })
     }
 
+    /// Like `from_password`, but draws both salts and the `IV` from the given random
+    /// number generator.
+    pub fn from_password_with_rng<R: RngCore + CryptoRng>(password: &str,
+                                                          rng: &mut R)
+                                                          -> Result<Encryptor> {
+        let es = Salt::new_with_rng(rng)?;
+        let hs = Salt::new_with_rng(rng)?;
+        let iv = IV::new_with_rng(rng)?;
+        Encryptor::from_password(password, es, hs, iv)
+    }
+
     pub fn from_keys(ek: EncryptionKey, hk: HMACKey, iv: IV) -> Result<Encryptor> {
 
         let mut header: Vec<u8> = Vec::new();
@@ -56,55 +74,140 @@ impl Encryptor {
         })
     }
 
-    pub fn cipher_text<X: blockmodes::PaddingProcessor + Send + 'static>(&self, padding: X, plain_text: &PlainText) -> Result<CipherText> {
-        let iv = self.iv.to_vec();
-        let key = self.encryption_key.to_vec();
-        let mut encryptor = aes::cbc_encryptor(
-            aes::KeySize::KeySize256,
-            key,
-            iv,
-            padding);
-
-        // Usage taken from: https://github.com/DaGenix/rust-crypto/blob/master/examples/symmetriccipher.rs
-        let mut final_result = Vec::<u8>::new();
-        let mut buffer = [0; 4096];
-        let mut write_buffer = RefWriteBuffer::new(&mut buffer);
-        let mut read_buffer  = RefReadBuffer::new(plain_text);
-
-        loop {
-            let result = try!(encryptor.encrypt(&mut read_buffer, &mut write_buffer, true)
-                              .map_err(ErrorKind::EncryptionFailed));
-            final_result.extend(write_buffer.take_read_buffer().take_remaining().iter().map(|&i| i));
-
-            match result {
-                BufferResult::BufferUnderflow => break,
-                BufferResult::BufferOverflow => { }
+    /// Like `from_keys`, but draws the `IV` from the given random number generator.
+    pub fn from_keys_with_rng<R: RngCore + CryptoRng>(ek: EncryptionKey,
+                                                      hk: HMACKey,
+                                                      rng: &mut R)
+                                                      -> Result<Encryptor> {
+        let iv = IV::new_with_rng(rng)?;
+        Encryptor::from_keys(ek, hk, iv)
+    }
+
+    /// Builds an `Encryptor` for the given `Credentials`, drawing the salts (if needed) and the
+    /// `IV` from the given random number generator.
+    pub fn from_credentials_with_rng<R: RngCore + CryptoRng>(credentials: &Credentials,
+                                                             rng: &mut R)
+                                                             -> Result<Encryptor> {
+        match *credentials {
+            Credentials::Password(password) => Encryptor::from_password_with_rng(password, rng),
+            Credentials::Keys(ref ek, ref hk) => {
+                Encryptor::from_keys_with_rng(ek.clone(), hk.clone(), rng)
             }
         }
+    }
 
-        Ok(CipherText(final_result))
+    /// Builds an `Encryptor` for the given `Credentials`, with random salts and `IV`.
+    #[cfg(feature = "std")]
+    pub fn from_credentials(credentials: &Credentials) -> Result<Encryptor> {
+        Encryptor::from_credentials_with_rng(credentials, &mut OsRng)
+    }
 
+    #[cfg(feature = "std")]
+    pub(crate) fn encryption_key(&self) -> &EncryptionKey {
+        &self.encryption_key
     }
 
-    pub fn encrypt(&self, plain_text: &PlainText) -> Result<Message> {
+    #[cfg(feature = "std")]
+    pub(crate) fn hmac_key(&self) -> &HMACKey {
+        &self.hmac_key
+    }
 
-        // If the input is empty, use the Pkcs7 padding as input.
-        let cipher_text = match plain_text.is_empty() {
-            true  => try!(self.cipher_text(blockmodes::NoPadding, vec![16;16].as_slice())),
-            false => try!(self.cipher_text(blockmodes::PkcsPadding, &plain_text)),
-        };
+    #[cfg(feature = "std")]
+    pub(crate) fn header(&self) -> &Header {
+        &self.header
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn iv(&self) -> &IV {
+        &self.iv
+    }
 
-        let CipherText(ref text) = cipher_text;
+    /// The exact size of the `Message` produced by encrypting `plain_text_len` bytes.
+    pub fn message_len(&self, plain_text_len: usize) -> usize {
+        let Header(ref header) = self.header;
+        header.len() + (plain_text_len / 16 + 1) * 16 + 32
+    }
 
-        let HMAC(hmac) = try!(HMAC::new(&self.header, text.as_slice(), &self.hmac_key));
+    /// Encrypts `plain_text` with AES-256-CBC and PKCS#7 padding, without any header or HMAC.
+    pub fn cipher_text(&self, plain_text: &PlainText) -> Result<CipherText> {
+        let mut buffer = vec![0; (plain_text.len() / 16 + 1) * 16];
+        let len = self.cipher_text_into(plain_text, &mut buffer)?;
+        buffer.truncate(len);
+        Ok(CipherText(buffer))
+    }
 
-        let mut message = Vec::new();
+    fn cipher_text_into(&self, plain_text: &PlainText, out: &mut [u8]) -> Result<usize> {
+        let encryptor = Aes256CbcEnc::new_from_slices(self.encryption_key.to_vec(),
+                                                      self.iv.as_slice())
+            .map_err(|_| ErrorKind::EncryptionFailed(SymmetricCipherError::InvalidLength))?;
+
+        out[..plain_text.len()].copy_from_slice(plain_text);
+        let cipher_text = encryptor.encrypt_padded_mut::<Pkcs7>(out, plain_text.len())
+            .map_err(|_| ErrorKind::EncryptionFailed(SymmetricCipherError::InvalidLength))?;
+        Ok(cipher_text.len())
+    }
+
+    /// Encrypts `plain_text` directly into `out`, without allocating the `Message`. Returns the
+    /// number of bytes written, or `ErrorKind::BufferTooSmall` if `out` is shorter than
+    /// `message_len`.
+    pub fn encrypt_into(&self, plain_text: &PlainText, out: &mut [u8]) -> Result<usize> {
+        self.encrypt_into_with(plain_text, None, out)
+    }
+
+    fn encrypt_into_with(&self,
+                         plain_text: &PlainText,
+                         associated_data: Option<&[u8]>,
+                         out: &mut [u8])
+                         -> Result<usize> {
+        let message_len = self.message_len(plain_text.len());
+        if out.len() < message_len {
+            return Err(Error::new(ErrorKind::BufferTooSmall(message_len),
+                                  "Output buffer too small.".to_owned()));
+        }
 
         let Header(ref header) = self.header;
-        message.extend(header.as_slice());
-        message.extend(text);
-        message.extend(hmac.as_slice());
+        out[..header.len()].copy_from_slice(header);
+
+        let text_len = {
+            let (_, rest) = out.split_at_mut(header.len());
+            self.cipher_text_into(plain_text, &mut rest[..message_len - header.len() - 32])?
+        };
+
+        let hmac_position = header.len() + text_len;
+        let cipher_text = &out[header.len()..hmac_position];
+        let HMAC(hmac) = match associated_data {
+            None => HMAC::new(&self.header, cipher_text, &self.hmac_key)?,
+            Some(associated_data) => {
+                HMAC::with_associated_data(&self.header,
+                                           cipher_text,
+                                           associated_data,
+                                           &self.hmac_key)?
+            }
+        };
+        out[hmac_position..hmac_position + hmac.len()].copy_from_slice(&hmac);
+
+        Ok(hmac_position + hmac.len())
+    }
+
+    pub fn encrypt(&self, plain_text: &PlainText) -> Result<Message> {
+        let mut message = vec![0; self.message_len(plain_text.len())];
+        let len = self.encrypt_into(plain_text, &mut message)?;
+        message.truncate(len);
+        Ok(message)
+    }
 
+    /// Like `encrypt`, but also authenticates `associated_data` (which isn't part of the
+    /// `Message`), so that decrypting fails unless the same `associated_data` is supplied.
+    ///
+    /// **Not part of the v3 spec**: the result can only be decrypted with
+    /// `Decryptor::decrypt_with_associated_data`, see `v3::aad`.
+    pub fn encrypt_with_associated_data(&self,
+                                        plain_text: &PlainText,
+                                        associated_data: &[u8])
+                                        -> Result<Message> {
+        let mut message = vec![0; self.message_len(plain_text.len())];
+        let len = self.encrypt_into_with(plain_text, Some(associated_data), &mut message)?;
+        message.truncate(len);
         Ok(message)
     }
 }
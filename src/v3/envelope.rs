@@ -0,0 +1,222 @@
+//! Envelope encryption: every object is encrypted with its own random data keys, which are in
+//! turn wrapped by a master key held by a `KeyManager`.
+//!
+//! An envelope is laid out as:
+//!
+//! ```text
+//! "RNCE" | version (1) | key id length (1) | key id (UTF-8) | wrapped key length (u16, BE) |
+//! wrapped key | v3 key-based message
+//! ```
+//!
+//! The data keys are a `KeyPair`, so the payload is a regular key-based `Message`, produced by
+//! `Encryptor::from_keys`. The key id and the wrapped key aren't authenticated on their own, but
+//! tampering with either makes unwrapping or the payload HMAC validation fail.
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::decryptor::Decryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::keys::KeyPair;
+use v3::rng::{RngCore, CryptoRng};
+#[cfg(feature = "std")]
+use v3::rng::OsRng;
+
+#[cfg(feature = "std")]
+pub use self::local::LocalKeyManager;
+
+/// The magic bytes starting every envelope.
+pub const MAGIC: &[u8; 4] = b"RNCE";
+/// The version of the envelope format.
+pub const VERSION: u8 = 1;
+
+/// Wraps and unwraps data keys with master keys, identified by a key id, which never leave it.
+///
+/// Implementations typically delegate to a KMS or an HSM; `LocalKeyManager` keeps the master
+/// keys in local files, for tests and development.
+pub trait KeyManager {
+    /// Encrypts `data_key` with the master key `key_id`.
+    fn wrap(&self, key_id: &str, data_key: &[u8]) -> Result<Vec<u8>>;
+
+    /// Decrypts a data key wrapped by `wrap` with the same `key_id`.
+    fn unwrap(&self, key_id: &str, wrapped_key: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// A payload encrypted with data keys, along with these keys wrapped by a master key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Envelope {
+    pub key_id: String,
+    pub wrapped_key: Vec<u8>,
+    pub message: Message,
+}
+
+fn invalid_format(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidFormat, message.to_owned())
+}
+
+impl Envelope {
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.key_id.len() > u8::MAX as usize || self.wrapped_key.len() > u16::MAX as usize {
+            return Err(invalid_format("The key id or the wrapped key is too long."));
+        }
+
+        let mut bytes = Vec::with_capacity(8 + self.key_id.len() + self.wrapped_key.len() +
+                                           self.message.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(self.key_id.len() as u8);
+        bytes.extend_from_slice(self.key_id.as_bytes());
+        bytes.extend_from_slice(&(self.wrapped_key.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&self.wrapped_key);
+        bytes.extend_from_slice(&self.message);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Envelope> {
+        if bytes.len() < 6 || &bytes[..4] != MAGIC {
+            return Err(invalid_format("Not an envelope."));
+        }
+        if bytes[4] != VERSION {
+            return Err(invalid_format("Unsupported envelope version."));
+        }
+
+        let id_end = 6 + bytes[5] as usize;
+        if bytes.len() < id_end + 2 {
+            return Err(Error::new(ErrorKind::NotEnoughInput(bytes.len()),
+                                  "Truncated envelope.".to_owned()));
+        }
+        let key_id = core::str::from_utf8(&bytes[6..id_end])
+            .map_err(|_| invalid_format("The key id isn't valid UTF-8."))?;
+        let wrapped_end = id_end + 2 +
+                          u16::from_be_bytes([bytes[id_end], bytes[id_end + 1]]) as usize;
+        if bytes.len() < wrapped_end {
+            return Err(Error::new(ErrorKind::NotEnoughInput(bytes.len()),
+                                  "Truncated envelope.".to_owned()));
+        }
+
+        Ok(Envelope {
+            key_id: key_id.to_owned(),
+            wrapped_key: bytes[id_end + 2..wrapped_end].to_vec(),
+            message: bytes[wrapped_end..].to_vec(),
+        })
+    }
+}
+
+/// Encrypts `plain_text` with fresh data keys wrapped by the master key `key_id` of
+/// `key_manager`, drawing the data keys and the `IV` from the given random number generator.
+pub fn seal_with_rng<K, R>(key_manager: &K,
+                           key_id: &str,
+                           plain_text: &PlainText,
+                           rng: &mut R)
+                           -> Result<Envelope>
+    where K: KeyManager + ?Sized,
+          R: RngCore + CryptoRng
+{
+    let data_keys = KeyPair::generate_with_rng(rng)?;
+    let wrapped_key = key_manager.wrap(key_id, &data_keys.to_bytes())?;
+    let message = Encryptor::from_keys_with_rng(data_keys.encryption_key(),
+                                                data_keys.hmac_key(),
+                                                rng)?
+        .encrypt(plain_text)?;
+
+    Ok(Envelope {
+        key_id: key_id.to_owned(),
+        wrapped_key,
+        message,
+    })
+}
+
+/// Encrypts `plain_text` with fresh data keys wrapped by the master key `key_id` of
+/// `key_manager`.
+#[cfg(feature = "std")]
+pub fn seal<K: KeyManager + ?Sized>(key_manager: &K,
+                                    key_id: &str,
+                                    plain_text: &PlainText)
+                                    -> Result<Envelope> {
+    seal_with_rng(key_manager, key_id, plain_text, &mut OsRng)
+}
+
+/// Unwraps the data keys of `envelope` with `key_manager`, then decrypts its payload.
+pub fn open<K: KeyManager + ?Sized>(key_manager: &K, envelope: &Envelope) -> Result<Vec<u8>> {
+    let data_keys = KeyPair::from_bytes(&key_manager.unwrap(&envelope.key_id,
+                                                            &envelope.wrapped_key)?)?;
+    Decryptor::from_keys(data_keys.encryption_key(),
+                         data_keys.hmac_key(),
+                         &envelope.message)?
+        .decrypt(&envelope.message)
+}
+
+#[cfg(feature = "std")]
+mod local {
+    use std::fs;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::vec::Vec;
+    use std::borrow::ToOwned;
+
+    use v3::encryptor::Encryptor;
+    use v3::decryptor::Decryptor;
+    use v3::errors::{Result, Error, ErrorKind};
+    use v3::keys::KeyPair;
+    use v3::rng::OsRng;
+    use super::KeyManager;
+
+    /// A `KeyManager` keeping its master keys as keyfiles (see `v3::keys`) named
+    /// `<key id>.key` in a directory, wrapping data keys as key-based v3 messages.
+    ///
+    /// **The master keys are stored in clear**: this is only meant for tests and development.
+    pub struct LocalKeyManager {
+        dir: PathBuf,
+    }
+
+    impl LocalKeyManager {
+        /// Uses the master keys in `dir`, which is created if needed.
+        pub fn open<P: Into<PathBuf>>(dir: P) -> Result<LocalKeyManager> {
+            let dir = dir.into();
+            fs::create_dir_all(&dir)?;
+            Ok(LocalKeyManager { dir })
+        }
+
+        fn path(&self, key_id: &str) -> Result<PathBuf> {
+            let valid = !key_id.is_empty() && key_id.len() <= u8::MAX as usize &&
+                        key_id.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b)) &&
+                        !key_id.starts_with('.');
+            if !valid {
+                return Err(Error::new(ErrorKind::InvalidFormat,
+                                      "Key ids are made of ASCII letters, digits, '-', '_' and '.'."
+                                          .to_owned()));
+            }
+            Ok(self.dir.join(format!("{}.key", key_id)))
+        }
+
+        fn master_key(&self, key_id: &str) -> Result<KeyPair> {
+            KeyPair::from_keyfile(&fs::read_to_string(self.path(key_id)?)?)
+        }
+
+        /// Generates a new master key `key_id`, failing if it already exists.
+        pub fn create_key(&self, key_id: &str) -> Result<()> {
+            let path = self.path(key_id)?;
+            let keyfile = KeyPair::generate()?.to_keyfile();
+            let mut file = fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+            file.write_all(keyfile.as_bytes())?;
+            Ok(())
+        }
+    }
+
+    impl KeyManager for LocalKeyManager {
+        fn wrap(&self, key_id: &str, data_key: &[u8]) -> Result<Vec<u8>> {
+            let master = self.master_key(key_id)?;
+            Encryptor::from_keys_with_rng(master.encryption_key(), master.hmac_key(), &mut OsRng)?
+                .encrypt(data_key)
+        }
+
+        fn unwrap(&self, key_id: &str, wrapped_key: &[u8]) -> Result<Vec<u8>> {
+            let master = self.master_key(key_id)?;
+            Decryptor::from_keys(master.encryption_key(), master.hmac_key(), wrapped_key)?
+                .decrypt(wrapped_key)
+        }
+    }
+}
@@ -1,11 +1,21 @@
+use core::result::Result as StdResult;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use std::io;
 
-extern crate crypto;
-
-use std::result::Result as StdResult;
-use std;
+use v3::rng::RngError;
 
 pub type Result<T> = StdResult<T, Error>;
 
+/// The errors raised by the block cipher layer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymmetricCipherError {
+    /// The key, the `IV` or the cipher text had the wrong length.
+    InvalidLength,
+    /// The PKCS#7 padding of the decrypted plain text was invalid.
+    InvalidPadding,
+}
+
 /// All the things which can go wrong :)
 #[derive(Debug)]
 pub enum ErrorKind {
@@ -19,14 +29,31 @@ pub enum ErrorKind {
     WrongInputSize(usize),
     /// Not enough input for decryption.
     NotEnoughInput(usize),
+    /// The output buffer was too small, it needs to be at least of the given size.
+    BufferTooSmall(usize),
     /// The IV generation failed.
-    IVGenerationFailed(std::io::Error),
+    IVGenerationFailed(RngError),
     /// The Salt generation failed.
-    SaltGenerationFailed(std::io::Error),
+    SaltGenerationFailed(RngError),
+    /// The generation of random keys failed.
+    KeyGenerationFailed(RngError),
     /// The encryption failed, due to an error raised from the downstream crypto layer.
-    EncryptionFailed(crypto::symmetriccipher::SymmetricCipherError),
+    EncryptionFailed(SymmetricCipherError),
     /// The decryption failed, due to an error raised from the downstream crypto layer.
-    DecryptionFailed(crypto::symmetriccipher::SymmetricCipherError),
+    DecryptionFailed(SymmetricCipherError),
+    /// The input isn't laid out as expected (wrong magic bytes, version, length or sequence).
+    InvalidFormat,
+    /// The output would exceed the given limit, in bytes.
+    LimitExceeded(usize),
+    /// The signature didn't match, or wasn't made by a trusted key.
+    SignatureValidationFailed,
+    /// The token was valid, but its time to live has elapsed.
+    TokenExpired,
+    /// The token claims to be issued in the future, beyond the tolerated clock skew.
+    TokenNotYetValid,
+    /// Reading or writing a stream failed.
+    #[cfg(feature = "std")]
+    Io(io::Error),
 }
 
 #[derive(Debug)]
@@ -52,3 +79,22 @@ impl Error {
         }
     }
 }
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::new(ErrorKind::Io(e), String::from("I/O error."))
+    }
+}
+
+/// Turns an `Error` back into an `io::Error`, for the `Read` and `Write` adapters: `Io` errors
+/// are unwrapped, and anything else becomes an `io::ErrorKind::InvalidData` error.
+#[cfg(feature = "std")]
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        match e.kind {
+            ErrorKind::Io(e) => e,
+            kind => io::Error::new(io::ErrorKind::InvalidData, format!("{} ({:?})", e.message, kind)),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+extern crate hkdf;
+extern crate pbkdf2;
+extern crate sha1;
+extern crate sha2;
+
+use self::hkdf::Hkdf;
+use self::pbkdf2::pbkdf2_hmac;
+use self::sha1::Sha1;
+use self::sha2::Sha256;
+use alloc::borrow::ToOwned;
+
+use v3::types::{Password, Salt, EncryptionSalt, HMACSalt, EncryptionKey, HMACKey};
+use v3::errors::{Result, Error, ErrorKind};
+
+/// The number of PBKDF2 iterations mandated by the v3 spec.
+pub const DEFAULT_ITERATIONS: u32 = 10_000;
+
+/// The length (in bytes) of every key derived by this module.
+pub const KEY_LEN: usize = 32;
+
+/// Derives a 32 bytes key out of a password and a `Salt`, using PBKDF2-HMAC-SHA1 with
+/// the 10,000 iterations required by the v3 spec.
+pub fn derive_key(password: &Password, salt: &Salt) -> [u8; KEY_LEN] {
+    derive_key_with_iterations(password, salt, DEFAULT_ITERATIONS)
+}
+
+/// Like `derive_key`, but with an explicit number of `iterations`. Messages produced with keys
+/// derived with anything but `DEFAULT_ITERATIONS` won't be readable by other v3 implementations
+/// using the password-based API, so this is only meant for legacy or compatibility purposes.
+pub fn derive_key_with_iterations(password: &Password,
+                                  salt: &Salt,
+                                  iterations: u32)
+                                  -> [u8; KEY_LEN] {
+    let mut result = [0; KEY_LEN];
+    pbkdf2_hmac::<Sha1>(password, salt.as_slice(), iterations, &mut result);
+    result
+}
+
+/// Derives both the `EncryptionKey` and the `HMACKey` for a password, exactly like
+/// the password-based `Encryptor` and `Decryptor` do.
+pub fn derive_keys(password: &Password,
+                   encryption_salt: &EncryptionSalt,
+                   hmac_salt: &HMACSalt)
+                   -> (EncryptionKey, HMACKey) {
+    (EncryptionKey::from(derive_key(password, encryption_salt).to_vec()),
+     HMACKey::from(derive_key(password, hmac_salt).to_vec()))
+}
+
+/// The minimum length of the master secret accepted by `hkdf_keys`: it can't be weaker than the
+/// keys derived from it.
+pub const MIN_MASTER_SECRET_LEN: usize = KEY_LEN;
+
+/// Derives an `EncryptionKey` and an `HMACKey` for the key-based API out of a single master
+/// secret, with HKDF-SHA256 (RFC 5869) and no salt. Each distinct `info` (a tenant, a table, a
+/// file type...) gets its own, independent pair of keys.
+pub fn hkdf_keys(master_secret: &[u8], info: &[u8]) -> Result<(EncryptionKey, HMACKey)> {
+    hkdf_keys_with_salt(master_secret, &[], info)
+}
+
+/// Like `hkdf_keys`, with an explicit HKDF `salt`.
+///
+/// The 64 bytes of output keying material are split into the `EncryptionKey`, then the
+/// `HMACKey`.
+pub fn hkdf_keys_with_salt(master_secret: &[u8],
+                           salt: &[u8],
+                           info: &[u8])
+                           -> Result<(EncryptionKey, HMACKey)> {
+    if master_secret.len() < MIN_MASTER_SECRET_LEN {
+        return Err(Error::new(ErrorKind::WrongInputSize(master_secret.len()),
+                              "The master secret is too short.".to_owned()));
+    }
+
+    let salt = if salt.is_empty() { None } else { Some(salt) };
+    let mut okm = [0; 2 * KEY_LEN];
+    Hkdf::<Sha256>::new(salt, master_secret).expand(info, &mut okm).map_err(|_| {
+        Error::new(ErrorKind::WrongInputSize(okm.len()),
+                   "HKDF output too long.".to_owned())
+    })?;
+    Ok((EncryptionKey::from(okm[..KEY_LEN].to_vec()),
+        HMACKey::from(okm[KEY_LEN..].to_vec())))
+}
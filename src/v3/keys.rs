@@ -0,0 +1,184 @@
+//! Key pairs for the key-based API: generation, and import/export in hex, base64 or keyfiles.
+//!
+//! A keyfile is a single line, `rncryptor-keypair:v1:` followed by the 64 bytes of key material
+//! (the encryption key, then the HMAC key) in standard, padded base64.
+
+extern crate base64;
+
+use core::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use core::str::FromStr;
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use self::base64::Engine;
+use self::base64::engine::general_purpose::STANDARD;
+
+use v3::types::*;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::kdf::KEY_LEN;
+use v3::rng::{RngCore, CryptoRng};
+#[cfg(feature = "std")]
+use v3::rng::OsRng;
+
+/// The prefix of the current keyfile format.
+pub const KEYFILE_PREFIX: &str = "rncryptor-keypair:v1:";
+
+/// An `EncryptionKey` and an `HMACKey` of `KEY_LEN` bytes each.
+///
+/// `Display` prints the 64 bytes of key material in hex, or in base64 with the alternate flag
+/// (`{:#}`); `FromStr` accepts both. `Debug` never prints the keys.
+#[derive(Clone, PartialEq, Eq)]
+pub struct KeyPair {
+    encryption_key: Vec<u8>,
+    hmac_key: Vec<u8>,
+}
+
+fn wrong_size(len: usize) -> Error {
+    Error::new(ErrorKind::WrongInputSize(len),
+               format!("Keys must be {} bytes long.", KEY_LEN))
+}
+
+fn invalid_format(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidFormat, message.to_owned())
+}
+
+impl KeyPair {
+    /// Builds a `KeyPair` out of existing keys, which must be `KEY_LEN` bytes long.
+    pub fn new(encryption_key: EncryptionKey, hmac_key: HMACKey) -> Result<KeyPair> {
+        for len in &[encryption_key.to_vec().len(), hmac_key.to_vec().len()] {
+            if *len != KEY_LEN {
+                return Err(wrong_size(*len));
+            }
+        }
+        Ok(KeyPair {
+            encryption_key: encryption_key.to_vec().clone(),
+            hmac_key: hmac_key.to_vec().clone(),
+        })
+    }
+
+    /// Builds a `KeyPair` out of 64 bytes of key material: the encryption key, then the HMAC key.
+    pub fn from_bytes(bytes: &[u8]) -> Result<KeyPair> {
+        if bytes.len() != 2 * KEY_LEN {
+            return Err(wrong_size(bytes.len()));
+        }
+        Ok(KeyPair {
+            encryption_key: bytes[..KEY_LEN].to_vec(),
+            hmac_key: bytes[KEY_LEN..].to_vec(),
+        })
+    }
+
+    /// Generates a fresh `KeyPair`, drawn from the given random number generator.
+    pub fn generate_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> Result<KeyPair> {
+        let mut bytes = [0; 2 * KEY_LEN];
+        rng.try_fill_bytes(&mut bytes).map_err(|e| {
+            Error::new(ErrorKind::KeyGenerationFailed(e), "Key generation failed.".to_owned())
+        })?;
+        KeyPair::from_bytes(&bytes)
+    }
+
+    /// Generates a fresh, completely random `KeyPair`.
+    #[cfg(feature = "std")]
+    pub fn generate() -> Result<KeyPair> {
+        KeyPair::generate_with_rng(&mut OsRng)
+    }
+
+    pub fn encryption_key(&self) -> EncryptionKey {
+        EncryptionKey::from(self.encryption_key.clone())
+    }
+
+    pub fn hmac_key(&self) -> HMACKey {
+        HMACKey::from(self.hmac_key.clone())
+    }
+
+    /// The `Credentials` to encrypt or decrypt with this `KeyPair`.
+    pub fn credentials(&self) -> Credentials<'static> {
+        Credentials::Keys(self.encryption_key(), self.hmac_key())
+    }
+
+    /// The 64 bytes of key material: the encryption key, then the HMAC key.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.encryption_key.clone();
+        bytes.extend_from_slice(&self.hmac_key);
+        bytes
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.to_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn from_hex(input: &str) -> Result<KeyPair> {
+        let digits = input.as_bytes();
+        if digits.len() != 4 * KEY_LEN {
+            return Err(wrong_size(digits.len() / 2));
+        }
+
+        if !digits.iter().all(u8::is_ascii_hexdigit) {
+            return Err(invalid_format("Invalid hex."));
+        }
+
+        let bytes = digits.chunks(2)
+            .map(|pair| {
+                let digit = |d: u8| (d as char).to_digit(16).unwrap_or(0) as u8;
+                digit(pair[0]) << 4 | digit(pair[1])
+            })
+            .collect::<Vec<_>>();
+        KeyPair::from_bytes(&bytes)
+    }
+
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.to_bytes())
+    }
+
+    pub fn from_base64(input: &str) -> Result<KeyPair> {
+        let bytes = STANDARD.decode(input).map_err(|_| invalid_format("Invalid base64."))?;
+        KeyPair::from_bytes(&bytes)
+    }
+
+    /// Exports the `KeyPair` in the keyfile format, with a trailing newline.
+    pub fn to_keyfile(&self) -> String {
+        format!("{}{}\n", KEYFILE_PREFIX, self.to_base64())
+    }
+
+    /// Imports a `KeyPair` from a keyfile, ignoring surrounding whitespace.
+    pub fn from_keyfile(input: &str) -> Result<KeyPair> {
+        let input = input.trim();
+        if let Some(encoded) = input.strip_prefix(KEYFILE_PREFIX) {
+            return KeyPair::from_base64(encoded);
+        }
+        if input.starts_with("rncryptor-keypair:") {
+            Err(invalid_format("Unsupported keyfile version."))
+        } else {
+            Err(invalid_format("Not a keyfile."))
+        }
+    }
+}
+
+impl Display for KeyPair {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if f.alternate() {
+            f.write_str(&self.to_base64())
+        } else {
+            f.write_str(&self.to_hex())
+        }
+    }
+}
+
+/// Parses 128 hex digits, or 88 base64 characters.
+impl FromStr for KeyPair {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<KeyPair> {
+        if s.len() == 4 * KEY_LEN {
+            KeyPair::from_hex(s)
+        } else {
+            KeyPair::from_base64(s)
+        }
+    }
+}
+
+impl Debug for KeyPair {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.write_str("KeyPair(<redacted>)")
+    }
+}
@@ -0,0 +1,219 @@
+//! Password-protected keystores, keeping a `KeyPair` for the key-based API at rest.
+//!
+//! A keystore is a small text file:
+//!
+//! ```text
+//! rncryptor-keystore:v1
+//! id: 3f1c0e7a9b2d4c6e8f0a1b2c3d4e5f60
+//! label: invoices
+//! created: 1700000000
+//! sealed: AwEk...
+//! ```
+//!
+//! The metadata lines can be listed without the password, while `sealed` is the base64 of a
+//! password-based v3 `Message` holding the 64 bytes of key material followed by the metadata
+//! lines, so that unlocking also authenticates the metadata.
+
+extern crate base64;
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::string::{String, ToString};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+use std::borrow::ToOwned;
+
+use self::base64::Engine;
+use self::base64::engine::general_purpose::STANDARD;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::decryptor::Decryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::keys::KeyPair;
+use v3::kdf::KEY_LEN;
+use v3::atomic_file;
+use v3::rng::{RngCore, CryptoRng, OsRng};
+
+/// The first line of the current keystore format.
+pub const HEADER: &str = "rncryptor-keystore:v1";
+/// The extension of the keystore files picked up by `list`.
+pub const EXTENSION: &str = "rnkeystore";
+
+/// The metadata of a keystore, readable without its password.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeystoreInfo {
+    /// A random identifier, as 32 hex digits.
+    pub id: String,
+    /// A free-form, single line label.
+    pub label: String,
+    /// The creation time, in seconds since the Unix epoch.
+    pub created: u64,
+}
+
+impl KeystoreInfo {
+    fn lines(&self) -> String {
+        format!("id: {}\nlabel: {}\ncreated: {}\n", self.id, self.label, self.created)
+    }
+}
+
+/// A `KeyPair` sealed with a password, along with its metadata.
+#[derive(Clone, Debug)]
+pub struct Keystore {
+    info: KeystoreInfo,
+    sealed: Message,
+}
+
+fn invalid_format(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidFormat, message.to_owned())
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl Keystore {
+    /// Seals `keys` with `password`, drawing the id, the salts and the `IV` from the given random
+    /// number generator.
+    pub fn create_with_rng<R: RngCore + CryptoRng>(password: &str,
+                                                   label: &str,
+                                                   keys: &KeyPair,
+                                                   created: u64,
+                                                   rng: &mut R)
+                                                   -> Result<Keystore> {
+        if label.contains('\n') || label.contains('\r') {
+            return Err(invalid_format("Labels must fit on a single line."));
+        }
+
+        let mut id = [0; 16];
+        rng.try_fill_bytes(&mut id).map_err(ErrorKind::KeyGenerationFailed)?;
+        let info = KeystoreInfo {
+            id: id.iter().map(|b| format!("{:02x}", b)).collect(),
+            label: label.to_owned(),
+            created,
+        };
+
+        let mut plain_text = keys.to_bytes();
+        plain_text.extend_from_slice(info.lines().as_bytes());
+        let sealed = Encryptor::from_password_with_rng(password, rng)?.encrypt(&plain_text)?;
+        Ok(Keystore { info, sealed })
+    }
+
+    /// Seals `keys` with `password`, with a random id and the current time.
+    pub fn create(password: &str, label: &str, keys: &KeyPair) -> Result<Keystore> {
+        Keystore::create_with_rng(password, label, keys, now(), &mut OsRng)
+    }
+
+    pub fn info(&self) -> &KeystoreInfo {
+        &self.info
+    }
+
+    /// Decrypts the sealed plain text, checking that the metadata wasn't tampered with.
+    fn unseal(&self, password: &str) -> Result<Vec<u8>> {
+        let plain_text = Decryptor::from(password, &self.sealed)?.decrypt(&self.sealed)?;
+        if plain_text.len() < 2 * KEY_LEN ||
+           plain_text[2 * KEY_LEN..] != *self.info.lines().as_bytes() {
+            return Err(invalid_format("The keystore metadata doesn't match the sealed one."));
+        }
+        Ok(plain_text)
+    }
+
+    /// Unseals the `KeyPair`, checking that the metadata wasn't tampered with.
+    pub fn unlock(&self, password: &str) -> Result<KeyPair> {
+        KeyPair::from_bytes(&self.unseal(password)?[..2 * KEY_LEN])
+    }
+
+    /// Reseals the same `KeyPair` and metadata with `new_password`, drawing the salts and the
+    /// `IV` from the given random number generator.
+    pub fn change_password_with_rng<R: RngCore + CryptoRng>(&self,
+                                                            old_password: &str,
+                                                            new_password: &str,
+                                                            rng: &mut R)
+                                                            -> Result<Keystore> {
+        let plain_text = self.unseal(old_password)?;
+        let sealed = Encryptor::from_password_with_rng(new_password, rng)?.encrypt(&plain_text)?;
+        Ok(Keystore {
+            info: self.info.clone(),
+            sealed,
+        })
+    }
+
+    /// Reseals the same `KeyPair` and metadata with `new_password`.
+    pub fn change_password(&self, old_password: &str, new_password: &str) -> Result<Keystore> {
+        self.change_password_with_rng(old_password, new_password, &mut OsRng)
+    }
+
+    /// Reads the keystore at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Keystore> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Writes the keystore to `path`, through a synced temporary file so that an existing
+    /// keystore is never left half-written, even by a crash.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        atomic_file::write(path.as_ref(), self.to_string().as_bytes())
+    }
+}
+
+impl Display for Keystore {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f,
+               "{}\n{}sealed: {}\n",
+               HEADER,
+               self.info.lines(),
+               STANDARD.encode(&self.sealed))
+    }
+}
+
+impl FromStr for Keystore {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Keystore> {
+        let mut lines = s.lines();
+        match lines.next() {
+            Some(HEADER) => {}
+            Some(line) if line.starts_with("rncryptor-keystore:") => {
+                return Err(invalid_format("Unsupported keystore version."));
+            }
+            _ => return Err(invalid_format("Not a keystore.")),
+        }
+
+        let mut field = |name: &str| -> Result<String> {
+            lines.next()
+                .and_then(|line| line.strip_prefix(name))
+                .and_then(|line| line.strip_prefix(": "))
+                .map(|value| value.to_owned())
+                .ok_or_else(|| invalid_format("Missing or misplaced keystore field."))
+        };
+
+        let id = field("id")?;
+        let label = field("label")?;
+        let created = field("created")?
+            .parse()
+            .map_err(|_| invalid_format("Invalid keystore creation time."))?;
+        let sealed = STANDARD.decode(field("sealed")?)
+            .map_err(|_| invalid_format("Invalid base64."))?;
+
+        Ok(Keystore {
+            info: KeystoreInfo { id, label, created },
+            sealed,
+        })
+    }
+}
+
+/// Lists the keystores (the files ending in `.rnkeystore`) in `dir`, sorted by path, without
+/// unlocking them.
+pub fn list<P: AsRef<Path>>(dir: P) -> Result<Vec<(PathBuf, KeystoreInfo)>> {
+    let mut keystores = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == EXTENSION) {
+            let info = Keystore::load(&path)?.info;
+            keystores.push((path, info));
+        }
+    }
+    keystores.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(keystores)
+}
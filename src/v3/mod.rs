@@ -7,21 +7,88 @@ pub mod types;
 pub mod encryptor;
 ///! "Low-level" decryption abstractions.
 pub mod decryptor;
+///! PBKDF2 key derivation.
+pub mod kdf;
+///! Random number generators.
+pub mod rng;
+///! Key pairs generation, import and export.
+pub mod keys;
+///! Envelope encryption with pluggable key management.
+pub mod envelope;
+///! Messages which can be opened with any of several passwords.
+pub mod multi_password;
+///! Encryption bound to associated data (not part of the spec).
+pub mod aad;
+///! Expiring tokens.
+pub mod token;
+///! Convergent (deterministic) encryption, for deduplication.
+pub mod convergent;
+///! Plain text padding, to hide the length of messages.
+pub mod padding;
+///! Conversion from and to `openssl enc` files.
+pub mod openssl;
+///! Streaming encryption and decryption.
+#[cfg(feature = "std")]
+pub mod stream;
+///! Re-encryption under new credentials.
+#[cfg(feature = "std")]
+pub mod rekey;
+///! Seekable containers of independently encrypted chunks.
+#[cfg(feature = "std")]
+pub mod container;
+#[cfg(feature = "std")]
+mod atomic_file;
+///! Password-protected keystores.
+#[cfg(feature = "std")]
+pub mod keystore;
+///! Single-file vaults of named secrets.
+#[cfg(feature = "std")]
+pub mod vault;
+///! Encryption of whole directories as tar archives.
+#[cfg(feature = "archive")]
+pub mod archive;
+///! Compression before encryption.
+#[cfg(any(feature = "deflate", feature = "zstd"))]
+pub mod compression;
+///! Encrypted values inside configuration files.
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+pub mod config;
+///! Forensic recovery of damaged messages, bypassing the HMAC.
+#[cfg(feature = "salvage")]
+pub mod salvage;
+///! Encryption to X25519 public keys.
+#[cfg(feature = "x25519")]
+pub mod x25519;
+///! Ed25519 signatures over v3 messages.
+#[cfg(feature = "ed25519")]
+pub mod signed;
 
-use v3::types::{Salt, IV, PlainText, Message};
+use v3::types::{PlainText, Message};
 use v3::encryptor::{Encryptor};
 use v3::decryptor::{Decryptor};
 use v3::errors::{Result};
+use v3::rng::{RngCore, CryptoRng};
+#[cfg(feature = "std")]
+use v3::rng::OsRng;
+use alloc::vec::Vec;
 
 ///! Encrypts a `PlainText` with the given password, producing either an encrypted
 ///! `Message` or an `Error` otherwise.
 ///!
 ///! **Note: This is NOT a streaming function.**
+#[cfg(feature = "std")]
 pub fn encrypt(password: &str, plain_text: &PlainText) -> Result<Message> {
-    let esalt = try!(Salt::new());
-    let hsalt = try!(Salt::new());
-    let iv = try!(IV::new());
-    let encryptor = try!(Encryptor::from_password(password, esalt, hsalt, iv));
+    encrypt_with_rng(password, plain_text, &mut OsRng)
+}
+
+///! Like `encrypt`, but draws the salts and the `IV` from the given random number generator.
+///!
+///! **Note: This is NOT a streaming function.**
+pub fn encrypt_with_rng<R: RngCore + CryptoRng>(password: &str,
+                                                plain_text: &PlainText,
+                                                rng: &mut R)
+                                                -> Result<Message> {
+    let encryptor = Encryptor::from_password_with_rng(password, rng)?;
     encryptor.encrypt(plain_text)
 }
 
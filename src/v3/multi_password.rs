@@ -0,0 +1,200 @@
+//! Messages which can be opened with any of several passwords.
+//!
+//! The payload is encrypted once, as a key-based v3 `Message` with random keys, and each
+//! password gets a "key slot": a password-based v3 `Message` holding these 64 bytes of key
+//! material. Slots can be added and removed without re-encrypting the payload. The layout is:
+//!
+//! ```text
+//! "RNCM" | version (1) | slot count (1) | (slot length (u16, BE) | slot)* | payload
+//! ```
+//!
+//! Removing a slot only stops *future* copies from being opened with that password: whoever
+//! knew it may have kept the keys, so re-encrypt the payload with `seal` to actually revoke.
+
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::decryptor::Decryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::keys::KeyPair;
+use v3::rng::{RngCore, CryptoRng};
+#[cfg(feature = "std")]
+use v3::rng::OsRng;
+
+/// The magic bytes starting every multi-password message.
+pub const MAGIC: &[u8; 4] = b"RNCM";
+/// The version of the multi-password format.
+pub const VERSION: u8 = 1;
+/// The maximum number of key slots.
+pub const MAX_SLOTS: usize = u8::MAX as usize;
+
+/// A payload along with one key slot per password.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiPasswordMessage {
+    slots: Vec<Message>,
+    payload: Message,
+}
+
+fn invalid_format(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidFormat, message.to_owned())
+}
+
+fn truncated(len: usize) -> Error {
+    Error::new(ErrorKind::NotEnoughInput(len),
+               "Truncated multi-password message.".to_owned())
+}
+
+impl MultiPasswordMessage {
+    /// Encrypts `plain_text` with random keys, and adds a key slot for each of `passwords`,
+    /// drawing the keys, the salts and the `IV`s from the given random number generator.
+    pub fn seal_with_rng<R: RngCore + CryptoRng>(passwords: &[&str],
+                                                 plain_text: &PlainText,
+                                                 rng: &mut R)
+                                                 -> Result<MultiPasswordMessage> {
+        if passwords.is_empty() || passwords.len() > MAX_SLOTS {
+            return Err(Error::new(ErrorKind::WrongInputSize(passwords.len()),
+                                  "Between 1 and 255 passwords are needed.".to_owned()));
+        }
+
+        let keys = KeyPair::generate_with_rng(rng)?;
+        let payload = Encryptor::from_keys_with_rng(keys.encryption_key(), keys.hmac_key(), rng)?
+            .encrypt(plain_text)?;
+        let mut message = MultiPasswordMessage {
+            slots: Vec::with_capacity(passwords.len()),
+            payload,
+        };
+        for password in passwords {
+            message.push_slot(password, &keys, rng)?;
+        }
+        Ok(message)
+    }
+
+    /// Encrypts `plain_text` with random keys, and adds a key slot for each of `passwords`.
+    #[cfg(feature = "std")]
+    pub fn seal(passwords: &[&str], plain_text: &PlainText) -> Result<MultiPasswordMessage> {
+        MultiPasswordMessage::seal_with_rng(passwords, plain_text, &mut OsRng)
+    }
+
+    fn push_slot<R: RngCore + CryptoRng>(&mut self,
+                                         password: &str,
+                                         keys: &KeyPair,
+                                         rng: &mut R)
+                                         -> Result<()> {
+        if self.slots.len() == MAX_SLOTS {
+            return Err(Error::new(ErrorKind::WrongInputSize(MAX_SLOTS + 1),
+                                  "Too many key slots.".to_owned()));
+        }
+        let slot = Encryptor::from_password_with_rng(password, rng)?.encrypt(&keys.to_bytes())?;
+        self.slots.push(slot);
+        Ok(())
+    }
+
+    /// Finds the key slot opened by `password`, returning its index and the payload keys.
+    /// Each slot tried costs a PBKDF2 key derivation.
+    fn unlock(&self, password: &str) -> Result<(usize, KeyPair)> {
+        for (index, slot) in self.slots.iter().enumerate() {
+            let keys = Decryptor::from(password, slot).and_then(|d| d.decrypt(slot));
+            if let Ok(keys) = keys {
+                return Ok((index, KeyPair::from_bytes(&keys)?));
+            }
+        }
+        Err(Error::new(ErrorKind::HMACValidationFailed,
+                       "No key slot matches the password.".to_owned()))
+    }
+
+    /// Decrypts the payload with any of the passwords.
+    pub fn open(&self, password: &str) -> Result<Vec<u8>> {
+        let (_, keys) = self.unlock(password)?;
+        Decryptor::from_keys(keys.encryption_key(), keys.hmac_key(), &self.payload)?
+            .decrypt(&self.payload)
+    }
+
+    /// The number of key slots.
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// The index of the key slot opened by `password`.
+    pub fn slot_of(&self, password: &str) -> Result<usize> {
+        self.unlock(password).map(|(index, _)| index)
+    }
+
+    /// Adds a key slot for `new_password`, unlocking the payload keys with `existing_password`,
+    /// and drawing the salts and the `IV` from the given random number generator.
+    pub fn add_slot_with_rng<R: RngCore + CryptoRng>(&mut self,
+                                                     existing_password: &str,
+                                                     new_password: &str,
+                                                     rng: &mut R)
+                                                     -> Result<()> {
+        let (_, keys) = self.unlock(existing_password)?;
+        self.push_slot(new_password, &keys, rng)
+    }
+
+    /// Adds a key slot for `new_password`, unlocking the payload keys with `existing_password`.
+    #[cfg(feature = "std")]
+    pub fn add_slot(&mut self, existing_password: &str, new_password: &str) -> Result<()> {
+        self.add_slot_with_rng(existing_password, new_password, &mut OsRng)
+    }
+
+    /// Removes the key slot at `index` (see `slot_of`). The last slot can't be removed.
+    pub fn remove_slot(&mut self, index: usize) -> Result<()> {
+        if index >= self.slots.len() {
+            return Err(Error::new(ErrorKind::WrongInputSize(index),
+                                  "No such key slot.".to_owned()));
+        }
+        if self.slots.len() == 1 {
+            return Err(Error::new(ErrorKind::WrongInputSize(index),
+                                  "The last key slot can't be removed.".to_owned()));
+        }
+        self.slots.remove(index);
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let slots_len = self.slots.iter().map(|s| 2 + s.len()).sum::<usize>();
+        let mut bytes = Vec::with_capacity(6 + slots_len + self.payload.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(self.slots.len() as u8);
+        for slot in &self.slots {
+            bytes.extend_from_slice(&(slot.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(slot);
+        }
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<MultiPasswordMessage> {
+        if bytes.len() < 6 || &bytes[..4] != MAGIC {
+            return Err(invalid_format("Not a multi-password message."));
+        }
+        if bytes[4] != VERSION {
+            return Err(invalid_format("Unsupported multi-password message version."));
+        }
+        if bytes[5] == 0 {
+            return Err(invalid_format("A multi-password message needs at least one key slot."));
+        }
+
+        let mut slots = Vec::with_capacity(bytes[5] as usize);
+        let mut position = 6;
+        for _ in 0..bytes[5] {
+            if bytes.len() < position + 2 {
+                return Err(truncated(bytes.len()));
+            }
+            let len = u16::from_be_bytes([bytes[position], bytes[position + 1]]) as usize;
+            position += 2;
+            if bytes.len() < position + len {
+                return Err(truncated(bytes.len()));
+            }
+            slots.push(bytes[position..position + len].to_vec());
+            position += len;
+        }
+
+        Ok(MultiPasswordMessage {
+            slots,
+            payload: bytes[position..].to_vec(),
+        })
+    }
+}
@@ -0,0 +1,159 @@
+//! Conversion between the files of `openssl enc -aes-256-cbc -pbkdf2 -salt` and v3 messages.
+//!
+//! OpenSSL lays its files out as `"Salted__" | salt (8) | cipher text`, with the AES-256 key and
+//! the `IV` both drawn from `PBKDF2-HMAC-SHA256(password, salt, iterations)` (48 bytes: the key,
+//! then the `IV`), and PKCS#7 padding. The number of iterations isn't stored: it's 10,000 unless
+//! `-iter` was given.
+//!
+//! **OpenSSL files aren't authenticated**: a wrong password or a damaged file is only detected
+//! when the padding happens to be invalid (about 255 times out of 256), otherwise it decrypts
+//! to garbage. Once converted, the v3 `Message` is authenticated, but only vouches for what was
+//! decrypted.
+//!
+//! The crate has no command line tool, so there's no conversion subcommand either: `to_v3` and
+//! `from_v3` are meant to be called from the application (or a small binary of its own).
+
+extern crate aes;
+extern crate cbc;
+extern crate pbkdf2;
+extern crate sha2;
+
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+
+use self::cbc::cipher::{BlockDecryptMut, KeyIvInit};
+use self::cbc::cipher::block_padding::Pkcs7;
+use self::pbkdf2::pbkdf2_hmac;
+use self::sha2::Sha256;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::decryptor::Decryptor;
+use v3::errors::{Result, Error, ErrorKind, SymmetricCipherError};
+use v3::kdf::KEY_LEN;
+use v3::rng::{RngCore, CryptoRng};
+#[cfg(feature = "std")]
+use v3::rng::OsRng;
+
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+/// The magic bytes starting every salted OpenSSL file.
+pub const MAGIC: &[u8; 8] = b"Salted__";
+/// The number of PBKDF2 iterations of `openssl enc -pbkdf2`, unless given with `-iter`.
+pub const DEFAULT_ITERATIONS: u32 = 10_000;
+
+const SALT_LEN: usize = 8;
+const IV_LEN: usize = 16;
+const HEADER_LEN: usize = 8 + SALT_LEN;
+
+/// The AES-256 key and the `IV` OpenSSL derives from `password` and `salt`.
+fn derive_key_and_iv(password: &str, salt: &[u8], iterations: u32) -> Result<(Vec<u8>, IV)> {
+    if iterations == 0 {
+        return Err(Error::new(ErrorKind::WrongInputSize(0),
+                              "At least one PBKDF2 iteration is needed.".to_owned()));
+    }
+    let mut derived = [0; KEY_LEN + IV_LEN];
+    pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut derived);
+    Ok((derived[..KEY_LEN].to_vec(), IV::from(derived[KEY_LEN..].to_vec())))
+}
+
+/// Decrypts the output of `openssl enc -aes-256-cbc -pbkdf2 -salt -iter <iterations>`.
+///
+/// Fails with `ErrorKind::DecryptionFailed` if the padding is invalid, which is how a wrong
+/// password usually shows up (see the caveat above).
+pub fn decrypt(password: &str, data: &[u8], iterations: u32) -> Result<Vec<u8>> {
+    if data.len() < HEADER_LEN || &data[..8] != MAGIC {
+        return Err(Error::new(ErrorKind::InvalidFormat,
+                              "Not a salted OpenSSL file.".to_owned()));
+    }
+    let cipher_text = &data[HEADER_LEN..];
+    if cipher_text.is_empty() || !cipher_text.len().is_multiple_of(IV_LEN) {
+        return Err(ErrorKind::DecryptionFailed(SymmetricCipherError::InvalidLength).into());
+    }
+
+    let (key, iv) = derive_key_and_iv(password, &data[8..HEADER_LEN], iterations)?;
+    let mut plain_text = cipher_text.to_vec();
+    let len = Aes256CbcDec::new_from_slices(&key, iv.as_slice())
+        .map_err(|_| ErrorKind::DecryptionFailed(SymmetricCipherError::InvalidLength))?
+        .decrypt_padded_mut::<Pkcs7>(&mut plain_text)
+        .map_err(|_| ErrorKind::DecryptionFailed(SymmetricCipherError::InvalidPadding))?
+        .len();
+    plain_text.truncate(len);
+    Ok(plain_text)
+}
+
+/// Encrypts `plain_text` as `openssl enc -aes-256-cbc -pbkdf2 -salt -iter <iterations>` does,
+/// drawing the salt from the given random number generator.
+pub fn encrypt_with_rng<R: RngCore + CryptoRng>(password: &str,
+                                                plain_text: &PlainText,
+                                                iterations: u32,
+                                                rng: &mut R)
+                                                -> Result<Vec<u8>> {
+    let Salt(salt) = Salt::new_with_rng(rng)?;
+    let (key, iv) = derive_key_and_iv(password, &salt, iterations)?;
+    // Only the cipher text is used, which doesn't involve the HMAC key.
+    let CipherText(cipher_text) = Encryptor::from_keys(EncryptionKey::from(key),
+                                                       HMACKey::from(Vec::new()),
+                                                       iv)?
+        .cipher_text(plain_text)?;
+
+    let mut data = Vec::with_capacity(HEADER_LEN + cipher_text.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&cipher_text);
+    Ok(data)
+}
+
+/// Encrypts `plain_text` as `openssl enc -aes-256-cbc -pbkdf2 -salt -iter <iterations>` does.
+#[cfg(feature = "std")]
+pub fn encrypt(password: &str, plain_text: &PlainText, iterations: u32) -> Result<Vec<u8>> {
+    encrypt_with_rng(password, plain_text, iterations, &mut OsRng)
+}
+
+/// Converts an OpenSSL file, encrypted with `password` and `iterations`, into a v3 `Message`
+/// encrypted with `credentials`, drawing the salts and the `IV` from the given random number
+/// generator.
+pub fn to_v3_with_rng<R: RngCore + CryptoRng>(password: &str,
+                                              data: &[u8],
+                                              iterations: u32,
+                                              credentials: &Credentials,
+                                              rng: &mut R)
+                                              -> Result<Message> {
+    let plain_text = decrypt(password, data, iterations)?;
+    Encryptor::from_credentials_with_rng(credentials, rng)?.encrypt(&plain_text)
+}
+
+/// Converts an OpenSSL file, encrypted with `password` and `iterations`, into a v3 `Message`
+/// encrypted with `credentials`.
+#[cfg(feature = "std")]
+pub fn to_v3(password: &str,
+             data: &[u8],
+             iterations: u32,
+             credentials: &Credentials)
+             -> Result<Message> {
+    to_v3_with_rng(password, data, iterations, credentials, &mut OsRng)
+}
+
+/// Converts a v3 `Message`, once authenticated with `credentials`, into an OpenSSL file
+/// encrypted with `password` and `iterations`, drawing the salt from the given random number
+/// generator.
+pub fn from_v3_with_rng<R: RngCore + CryptoRng>(credentials: &Credentials,
+                                                message: &Message,
+                                                password: &str,
+                                                iterations: u32,
+                                                rng: &mut R)
+                                                -> Result<Vec<u8>> {
+    let plain_text = Decryptor::from_credentials(credentials, message)?.decrypt(message)?;
+    encrypt_with_rng(password, &plain_text, iterations, rng)
+}
+
+/// Converts a v3 `Message`, once authenticated with `credentials`, into an OpenSSL file
+/// encrypted with `password` and `iterations`.
+#[cfg(feature = "std")]
+pub fn from_v3(credentials: &Credentials,
+               message: &Message,
+               password: &str,
+               iterations: u32)
+               -> Result<Vec<u8>> {
+    from_v3_with_rng(credentials, message, password, iterations, &mut OsRng)
+}
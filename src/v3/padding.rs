@@ -0,0 +1,122 @@
+//! Padding of the plain text before encryption, so that the length of a `Message` doesn't give
+//! away which of a few known plain texts (templates, form letters...) it holds.
+//!
+//! The plain text sealed in the v3 `Message` is `"RNCP" | plain text length (u64, BE) | plain
+//! text | zeros`, padded so that the cipher text fills a whole bucket of the chosen `Padding`
+//! scheme. Being part of the plain text, the length is authenticated by the HMAC like everything
+//! else: stripping the padding can't be manipulated. Messages produced here are regular v3
+//! messages, but their plain text only makes sense to `padding::decrypt`.
+//!
+//! Padding only blurs lengths within a bucket: plain texts falling in different buckets can
+//! still be told apart. Padmé keeps the overhead under 12%, while hiding the least significant
+//! bits of the length; powers of two hide more, at up to twice the size.
+
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::decryptor::Decryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::rng::{RngCore, CryptoRng};
+#[cfg(feature = "std")]
+use v3::rng::OsRng;
+
+/// The magic bytes starting the plain text of every padded `Message`.
+pub const MAGIC: &[u8; 4] = b"RNCP";
+
+const MARKER_LEN: usize = 4 + 8;
+const BLOCK_LEN: usize = 16;
+
+/// How the length of the cipher text is rounded up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Padding {
+    /// Padmé (from the PURBs paper): only keeps the most significant bits of the length.
+    Padme,
+    /// The next power of two.
+    PowerOfTwo,
+    /// The next multiple of the given size, in bytes (rounded up to a multiple of 16).
+    Bucket(usize),
+}
+
+impl Padding {
+    fn round_up(&self, len: usize) -> Result<usize> {
+        let rounded = match *self {
+            Padding::Padme => padme(len),
+            Padding::PowerOfTwo => len.checked_next_power_of_two(),
+            Padding::Bucket(0) => {
+                return Err(Error::new(ErrorKind::WrongInputSize(0),
+                                      "The bucket size can't be 0.".to_owned()))
+            }
+            Padding::Bucket(size) => len.div_ceil(size).checked_mul(size),
+        };
+        rounded.and_then(|r| r.div_ceil(BLOCK_LEN).checked_mul(BLOCK_LEN))
+            .ok_or_else(|| {
+                Error::new(ErrorKind::LimitExceeded(usize::MAX),
+                           "The padded plain text would be too large.".to_owned())
+            })
+    }
+}
+
+fn padme(len: usize) -> Option<usize> {
+    if len < 2 {
+        return Some(len);
+    }
+    let exponent = usize::BITS - 1 - len.leading_zeros();
+    let significant_bits = u32::BITS - exponent.leading_zeros();
+    let mask = (1usize << (exponent - significant_bits)) - 1;
+    len.checked_add(mask).map(|l| l & !mask)
+}
+
+/// The length of the cipher text (without the header and the HMAC) once `plain_text_len` bytes
+/// are padded with `padding`.
+pub fn padded_len(padding: Padding, plain_text_len: usize) -> Result<usize> {
+    // At least one byte of PKCS#7 padding is added by the encryption.
+    padding.round_up(MARKER_LEN + plain_text_len + 1)
+}
+
+/// Pads `plain_text` with `padding`, then encrypts it, drawing the salts and the `IV` from the
+/// given random number generator.
+pub fn encrypt_with_rng<R: RngCore + CryptoRng>(credentials: &Credentials,
+                                                padding: Padding,
+                                                plain_text: &PlainText,
+                                                rng: &mut R)
+                                                -> Result<Message> {
+    // One byte short of the cipher text length, so that PKCS#7 adds a single byte.
+    let padded_len = padded_len(padding, plain_text.len())? - 1;
+    let mut padded = Vec::with_capacity(padded_len);
+    padded.extend_from_slice(MAGIC);
+    padded.extend_from_slice(&(plain_text.len() as u64).to_be_bytes());
+    padded.extend_from_slice(plain_text);
+    padded.resize(padded_len, 0);
+
+    Encryptor::from_credentials_with_rng(credentials, rng)?.encrypt(&padded)
+}
+
+/// Pads `plain_text` with `padding`, then encrypts it.
+#[cfg(feature = "std")]
+pub fn encrypt(credentials: &Credentials,
+               padding: Padding,
+               plain_text: &PlainText)
+               -> Result<Message> {
+    encrypt_with_rng(credentials, padding, plain_text, &mut OsRng)
+}
+
+/// Decrypts a `Message` produced by `padding::encrypt`, then strips the padding.
+pub fn decrypt(credentials: &Credentials, message: &Message) -> Result<Vec<u8>> {
+    let padded = Decryptor::from_credentials(credentials, message)?.decrypt(message)?;
+    if padded.len() < MARKER_LEN || &padded[..4] != MAGIC {
+        return Err(Error::new(ErrorKind::InvalidFormat,
+                              "The message wasn't padded by rncryptor.".to_owned()));
+    }
+
+    let mut len = [0; 8];
+    len.copy_from_slice(&padded[4..MARKER_LEN]);
+    let len = u64::from_be_bytes(len);
+    let end = (MARKER_LEN as u64).saturating_add(len);
+    if end > padded.len() as u64 || padded[end as usize..].iter().any(|&b| b != 0) {
+        return Err(Error::new(ErrorKind::InvalidFormat,
+                              "The padding doesn't match the length.".to_owned()));
+    }
+    Ok(padded[MARKER_LEN..end as usize].to_vec())
+}
@@ -0,0 +1,57 @@
+//! Re-encryption of a `Message` under new credentials, without materialising its plain text.
+
+use std::io::{Cursor, Read, Seek, Write};
+use std::vec::Vec;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::errors::Result;
+use v3::rng::{RngCore, CryptoRng, OsRng};
+use v3::stream::{EncryptWriter, MessageReader};
+
+/// Re-encrypts the `Message` read from `reader` (from its current position up to its end) with
+/// `new` credentials into `writer`, drawing the salts and the `IV` from the given random number
+/// generator. Returns the underlying writer.
+///
+/// The HMAC is validated with the `old` credentials before anything is written, so a wrong
+/// password, wrong keys or a tampered `Message` leave `writer` untouched. Only one chunk of
+/// plain text is in memory at any time.
+///
+/// Should `reader` be modified while re-encrypting, the second HMAC check fails and an error is
+/// returned. `writer` then holds a truncated `Message` without its last chunk nor any HMAC,
+/// which can't be decrypted, but must still be discarded.
+///
+/// Validating before decrypting takes two passes, hence the `Seek` bound: to re-encrypt from a
+/// non-seekable reader, read the `Message` into memory and use `rekey_message`.
+pub fn rekey_with_rng<R, W, G>(reader: R,
+                               old: &Credentials,
+                               new: &Credentials,
+                               writer: W,
+                               rng: &mut G)
+                               -> Result<W>
+    where R: Read + Seek,
+          W: Write,
+          G: RngCore + CryptoRng
+{
+    let mut message = MessageReader::new(old, reader)?;
+    message.verify()?;
+
+    let encryptor = Encryptor::from_credentials_with_rng(new, rng)?;
+    let mut writer = EncryptWriter::new(&encryptor, writer)?;
+    message.decrypt_to(&mut writer)?;
+    writer.finish()
+}
+
+/// Re-encrypts the `Message` read from `reader` with `new` credentials into `writer`, using
+/// random salts and `IV`. See `rekey_with_rng`.
+pub fn rekey<R, W>(reader: R, old: &Credentials, new: &Credentials, writer: W) -> Result<W>
+    where R: Read + Seek,
+          W: Write
+{
+    rekey_with_rng(reader, old, new, writer, &mut OsRng)
+}
+
+/// Re-encrypts an in-memory `Message` with `new` credentials.
+pub fn rekey_message(message: &Message, old: &Credentials, new: &Credentials) -> Result<Message> {
+    rekey(Cursor::new(message), old, new, Vec::new())
+}
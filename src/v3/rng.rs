@@ -0,0 +1,20 @@
+extern crate rand_core;
+#[cfg(feature = "test-rng")]
+extern crate rand_chacha;
+
+pub use self::rand_core::{RngCore, CryptoRng};
+pub use self::rand_core::Error as RngError;
+#[cfg(feature = "std")]
+pub use self::rand_core::OsRng;
+
+/// A deterministic, seedable generator, only meant to make tests reproducible.
+/// **Never use it to encrypt real data.**
+#[cfg(feature = "test-rng")]
+pub type SeededRng = self::rand_chacha::ChaCha20Rng;
+
+/// Creates a `SeededRng` out of a `seed`.
+#[cfg(feature = "test-rng")]
+pub fn seeded_rng(seed: u64) -> SeededRng {
+    use self::rand_core::SeedableRng;
+    SeededRng::seed_from_u64(seed)
+}
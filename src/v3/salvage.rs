@@ -0,0 +1,118 @@
+//! Forensic recovery of damaged messages, **bypassing the HMAC**.
+//!
+//! When a few bytes of a large `Message` are damaged (typically at the end of a file), the HMAC
+//! validation rejects it as a whole. `unauthenticated_salvage` decrypts every cipher block it
+//! can anyway, for data recovery only:
+//!
+//! * nothing it returns is authenticated: an attacker can change it at will, undetected;
+//! * in CBC, a damaged cipher block garbles its own plain text block and flips the same bits in
+//!   the next one, but nothing tells where the damage is. Only the blocks given away by a broken
+//!   PKCS#7 padding or by a truncation are reported as suspect: **every block is suspect** when
+//!   the HMAC doesn't validate.
+//!
+//! The result is a `Salvaged` rather than plain bytes, so that it can't be mistaken for the
+//! output of `decrypt`, which never falls back to salvaging.
+
+extern crate aes;
+extern crate cbc;
+
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+
+use self::cbc::cipher::{BlockDecryptMut, KeyIvInit};
+use self::cbc::cipher::block_padding::NoPadding;
+
+use v3::types::*;
+use v3::errors::{Result, Error, ErrorKind, SymmetricCipherError};
+
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+const BLOCK_LEN: usize = 16;
+const HMAC_LEN: usize = 32;
+
+/// What could be recovered from a damaged `Message`. **Unauthenticated**, see the module
+/// documentation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Salvaged {
+    /// The decrypted blocks, without the PKCS#7 padding if it was valid.
+    pub data: Vec<u8>,
+    /// Whether the HMAC validated, in which case `data` is exactly what `decrypt` returns.
+    pub hmac_valid: bool,
+    /// The indices of the 16-byte blocks of `data` known to be (likely) damaged.
+    pub suspect_blocks: Vec<usize>,
+    /// Whether the `Message` was cut short, losing the end of the cipher text, the HMAC, or
+    /// both.
+    pub truncated: bool,
+}
+
+/// Decrypts whatever can be decrypted of a damaged `message`, **without validating its HMAC**.
+///
+/// Fails only if `message` is too short to hold a header and a cipher block.
+pub fn unauthenticated_salvage(credentials: &Credentials, message: &[u8]) -> Result<Salvaged> {
+    let header_len = credentials.header_len();
+    if message.len() < header_len + BLOCK_LEN {
+        return Err(Error::new(ErrorKind::NotEnoughInput(message.len()),
+                              "Nothing to salvage, not enough input.".to_owned()));
+    }
+
+    let (encryption_key, hmac_key) = match *credentials {
+        Credentials::Password(password) => {
+            (EncryptionKey::new(&Salt(message[2..10].to_vec()), password.as_bytes()),
+             HMACKey::new(&Salt(message[10..18].to_vec()), password.as_bytes()))
+        }
+        Credentials::Keys(ref ek, ref hk) => (ek.clone(), hk.clone()),
+    };
+    let header = Header(message[..header_len].to_vec());
+    let iv = &message[header_len - BLOCK_LEN..header_len];
+    let body = &message[header_len..];
+
+    // An intact layout has whole cipher blocks followed by the HMAC: anything else was cut
+    // short, and only the whole blocks are kept.
+    let intact = body.len() >= BLOCK_LEN + HMAC_LEN &&
+                 (body.len() - HMAC_LEN).is_multiple_of(BLOCK_LEN);
+    let (cipher_text, hmac) = if intact {
+        body.split_at(body.len() - HMAC_LEN)
+    } else {
+        (&body[..body.len() / BLOCK_LEN * BLOCK_LEN], &[][..])
+    };
+
+    let computed_hmac = HMAC::new(&header, cipher_text, &hmac_key)?;
+    let hmac_valid = !hmac.is_empty() &&
+                     HMAC(hmac.to_vec()).is_equal_in_consistent_time_to(&computed_hmac);
+
+    let mut data = cipher_text.to_vec();
+    Aes256CbcDec::new_from_slices(encryption_key.to_vec(), iv)
+        .map_err(|_| ErrorKind::DecryptionFailed(SymmetricCipherError::InvalidLength))?
+        .decrypt_padded_mut::<NoPadding>(&mut data)
+        .map_err(|_| ErrorKind::DecryptionFailed(SymmetricCipherError::InvalidLength))?;
+
+    let blocks = data.len() / BLOCK_LEN;
+    let mut suspect_blocks = Vec::new();
+    if !intact {
+        // The last whole block may be made of HMAC bytes, or still carry the padding.
+        suspect_blocks.push(blocks - 1);
+    } else if let Some(len) = pkcs7_unpadded_len(&data) {
+        data.truncate(len);
+    } else if !hmac_valid {
+        // Broken padding: the last cipher block, or the one before it, was damaged.
+        suspect_blocks.extend(blocks.saturating_sub(2)..blocks);
+    }
+
+    Ok(Salvaged {
+        data,
+        hmac_valid,
+        suspect_blocks,
+        truncated: !intact,
+    })
+}
+
+fn pkcs7_unpadded_len(data: &[u8]) -> Option<usize> {
+    let padding = *data.last()? as usize;
+    let valid = (1..=BLOCK_LEN).contains(&padding) &&
+                data[data.len() - padding..].iter().all(|&b| b as usize == padding);
+    if valid {
+        Some(data.len() - padding)
+    } else {
+        None
+    }
+}
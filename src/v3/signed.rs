@@ -0,0 +1,249 @@
+//! Ed25519 signatures over v3 messages.
+//!
+//! The v3 HMAC proves that a message was made by someone knowing the password (or the keys), but
+//! not by whom. A `SignedMessage` adds the sender's Ed25519 signature over the complete v3
+//! `Message` (header, cipher text and HMAC), and `verify_and_decrypt` only runs the `Decryptor`
+//! once the signature checked out against a set of trusted keys. The layout is:
+//!
+//! ```text
+//! "RNCS" | version (1) | signer public key (32) | signature (64) | v3 message
+//! ```
+//!
+//! The signature covers everything but itself: the magic bytes, the version, the signer public
+//! key and the v3 message. Keys are serialized as single lines, `rncryptor-ed25519-signing:v1:`
+//! or `rncryptor-ed25519-verifying:v1:` followed by the 32 bytes of the key in standard, padded
+//! base64.
+
+extern crate base64;
+extern crate ed25519_dalek;
+
+use core::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use core::str::FromStr;
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+
+use self::base64::Engine;
+use self::base64::engine::general_purpose::STANDARD;
+use self::ed25519_dalek::{Signature, Signer};
+
+use v3::types::*;
+use v3::decryptor::Decryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::rng::{RngCore, CryptoRng};
+#[cfg(feature = "std")]
+use v3::rng::OsRng;
+
+/// The magic bytes starting every signed message.
+pub const MAGIC: &[u8; 4] = b"RNCS";
+/// The version of the signed message format.
+pub const VERSION: u8 = 1;
+/// The prefix of the current signing key format.
+pub const SIGNING_KEY_PREFIX: &str = "rncryptor-ed25519-signing:v1:";
+/// The prefix of the current verifying key format.
+pub const VERIFYING_KEY_PREFIX: &str = "rncryptor-ed25519-verifying:v1:";
+
+const KEY_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+const HEADER_LEN: usize = 5 + KEY_LEN + SIGNATURE_LEN;
+
+fn invalid_format(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidFormat, message.to_owned())
+}
+
+fn decode_key(input: &str, prefix: &str, what: &str) -> Result<[u8; KEY_LEN]> {
+    let encoded = input.trim().strip_prefix(prefix).ok_or_else(|| {
+        invalid_format(&format!("Not an Ed25519 {} key, or an unsupported version.", what))
+    })?;
+    let bytes = STANDARD.decode(encoded).map_err(|_| invalid_format("Invalid base64."))?;
+    if bytes.len() != KEY_LEN {
+        return Err(Error::new(ErrorKind::WrongInputSize(bytes.len()),
+                              format!("Ed25519 keys must be {} bytes long.", KEY_LEN)));
+    }
+    let mut key = [0; KEY_LEN];
+    key.copy_from_slice(&bytes);
+    Ok(key)
+}
+
+/// The bytes covered by the signature.
+fn signed_bytes(signer: &VerifyingKey, message: &Message) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(5 + KEY_LEN + message.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    bytes.extend_from_slice(signer.0.as_bytes());
+    bytes.extend_from_slice(message);
+    bytes
+}
+
+/// An Ed25519 public key, trusted to sign messages.
+///
+/// `Display` and `FromStr` use the format described in the module documentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyingKey(ed25519_dalek::VerifyingKey);
+
+impl VerifyingKey {
+    /// Fails with `ErrorKind::InvalidFormat` if `bytes` aren't a valid curve point.
+    pub fn from_bytes(bytes: &[u8; KEY_LEN]) -> Result<VerifyingKey> {
+        ed25519_dalek::VerifyingKey::from_bytes(bytes)
+            .map(VerifyingKey)
+            .map_err(|_| invalid_format("Invalid Ed25519 public key."))
+    }
+
+    pub fn to_bytes(&self) -> [u8; KEY_LEN] {
+        self.0.to_bytes()
+    }
+}
+
+impl Display for VerifyingKey {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}{}", VERIFYING_KEY_PREFIX, STANDARD.encode(self.0.as_bytes()))
+    }
+}
+
+impl FromStr for VerifyingKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<VerifyingKey> {
+        VerifyingKey::from_bytes(&decode_key(s, VERIFYING_KEY_PREFIX, "verifying")?)
+    }
+}
+
+/// An Ed25519 private key, signing messages on behalf of its `verifying_key`.
+///
+/// `Display` and `FromStr` use the format described in the module documentation. `Debug` never
+/// prints the key.
+#[derive(Clone)]
+pub struct SigningKey(ed25519_dalek::SigningKey);
+
+impl SigningKey {
+    /// Generates a fresh `SigningKey`, drawn from the given random number generator.
+    pub fn generate_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> Result<SigningKey> {
+        let mut bytes = [0; KEY_LEN];
+        rng.try_fill_bytes(&mut bytes).map_err(|e| {
+            Error::new(ErrorKind::KeyGenerationFailed(e), "Key generation failed.".to_owned())
+        })?;
+        Ok(SigningKey::from_bytes(&bytes))
+    }
+
+    /// Generates a fresh, completely random `SigningKey`.
+    #[cfg(feature = "std")]
+    pub fn generate() -> Result<SigningKey> {
+        SigningKey::generate_with_rng(&mut OsRng)
+    }
+
+    pub fn from_bytes(bytes: &[u8; KEY_LEN]) -> SigningKey {
+        SigningKey(ed25519_dalek::SigningKey::from_bytes(bytes))
+    }
+
+    pub fn to_bytes(&self) -> [u8; KEY_LEN] {
+        self.0.to_bytes()
+    }
+
+    /// The public key matching this signing key, to be handed out to recipients.
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(self.0.verifying_key())
+    }
+}
+
+impl Display for SigningKey {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}{}", SIGNING_KEY_PREFIX, STANDARD.encode(self.0.as_bytes()))
+    }
+}
+
+impl FromStr for SigningKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<SigningKey> {
+        decode_key(s, SIGNING_KEY_PREFIX, "signing").map(|bytes| SigningKey::from_bytes(&bytes))
+    }
+}
+
+impl Debug for SigningKey {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.write_str("SigningKey(<redacted>)")
+    }
+}
+
+/// A v3 `Message` along with its signer's public key and signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedMessage {
+    pub signer: VerifyingKey,
+    pub signature: [u8; SIGNATURE_LEN],
+    pub message: Message,
+}
+
+impl SignedMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.message.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(self.signer.0.as_bytes());
+        bytes.extend_from_slice(&self.signature);
+        bytes.extend_from_slice(&self.message);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SignedMessage> {
+        if bytes.len() < 5 || &bytes[..4] != MAGIC {
+            return Err(invalid_format("Not a signed message."));
+        }
+        if bytes[4] != VERSION {
+            return Err(invalid_format("Unsupported signed message version."));
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::new(ErrorKind::NotEnoughInput(bytes.len()),
+                                  "Truncated signed message.".to_owned()));
+        }
+
+        let mut signer = [0; KEY_LEN];
+        signer.copy_from_slice(&bytes[5..5 + KEY_LEN]);
+        let mut signature = [0; SIGNATURE_LEN];
+        signature.copy_from_slice(&bytes[5 + KEY_LEN..HEADER_LEN]);
+        Ok(SignedMessage {
+            signer: VerifyingKey::from_bytes(&signer)?,
+            signature,
+            message: bytes[HEADER_LEN..].to_vec(),
+        })
+    }
+}
+
+/// Signs the complete `message` (typically the output of an `Encryptor`) with `key`.
+pub fn sign(key: &SigningKey, message: &Message) -> SignedMessage {
+    let signer = key.verifying_key();
+    let signature = key.0.sign(&signed_bytes(&signer, message));
+    SignedMessage {
+        signer,
+        signature: signature.to_bytes(),
+        message: message.clone(),
+    }
+}
+
+/// Checks that `signed` was signed by one of the `trusted` keys, returning the v3 `Message`.
+///
+/// Fails with `ErrorKind::SignatureValidationFailed` if the signer isn't trusted, or if the
+/// signature doesn't match.
+pub fn verify<'a>(trusted: &[VerifyingKey], signed: &'a SignedMessage) -> Result<&'a Message> {
+    if !trusted.contains(&signed.signer) {
+        return Err(Error::new(ErrorKind::SignatureValidationFailed,
+                              "The message wasn't signed by a trusted key.".to_owned()));
+    }
+    let signature = Signature::from_bytes(&signed.signature);
+    signed.signer
+        .0
+        .verify_strict(&signed_bytes(&signed.signer, &signed.message), &signature)
+        .map_err(|_| {
+            Error::new(ErrorKind::SignatureValidationFailed,
+                       "The signature doesn't match.".to_owned())
+        })?;
+    Ok(&signed.message)
+}
+
+/// Checks the signature of `signed` against the `trusted` keys (see `verify`), and only then
+/// decrypts its v3 `Message` with `credentials`.
+pub fn verify_and_decrypt(trusted: &[VerifyingKey],
+                          credentials: &Credentials,
+                          signed: &SignedMessage)
+                          -> Result<Vec<u8>> {
+    let message = verify(trusted, signed)?;
+    Decryptor::from_credentials(credentials, message)?.decrypt(message)
+}
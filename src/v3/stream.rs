@@ -0,0 +1,425 @@
+//! Streaming encryption and decryption, which never hold a whole `Message` or plain text in
+//! memory.
+//!
+//! As the HMAC of a v3 `Message` is at its very end, decryption is done in two passes over a
+//! seekable input: the first one validates the HMAC, and only then the second one decrypts.
+//! Nothing is written to the output if the HMAC validation fails.
+//!
+//! The second pass is checked against the first one, but only once it's over: should the input
+//! change in between, the chunks decrypted before the change is detected are written anyway.
+//! The last chunk (with the end of the plain text) is always held back until then, but a
+//! decryption error still means that **whatever was written to the output must be discarded**.
+
+extern crate aes;
+extern crate cbc;
+extern crate hmac;
+extern crate sha2;
+
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::vec::Vec;
+use std::borrow::ToOwned;
+
+use self::cbc::cipher::{BlockEncryptMut, BlockDecryptMut, KeyIvInit};
+use self::cbc::cipher::generic_array::GenericArray;
+use self::hmac::{Hmac, Mac};
+use self::sha2::Sha256;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::errors::{Result, Error, ErrorKind, SymmetricCipherError};
+use v3::rng::{RngCore, CryptoRng, OsRng};
+
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+const BLOCK_LEN: usize = 16;
+const HMAC_LEN: usize = 32;
+const CHUNK_LEN: usize = 64 * 1024;
+
+fn new_mac(hmac_key: &HMACKey) -> Result<Hmac<Sha256>> {
+    Hmac::<Sha256>::new_from_slice(hmac_key.to_vec()).map_err(|_| {
+        Error::new(ErrorKind::HMACGenerationFailed,
+                   "HMAC Generation failed.".to_owned())
+    })
+}
+
+/// A `Write` adapter which encrypts everything written to it into a v3 `Message`.
+///
+/// The header is written as soon as the `EncryptWriter` is built, and the final (padded) block
+/// and the HMAC only when calling `finish`: dropping an `EncryptWriter` without finishing it
+/// leaves a truncated, invalid `Message` behind.
+///
+/// Once writing to the underlying writer fails, the error is reported again (with the same
+/// `io::ErrorKind`) by every later `write` and by `finish`, as the `Message` written so far can't
+/// be completed: it must be discarded.
+pub struct EncryptWriter<W: Write> {
+    inner: W,
+    cipher: Aes256CbcEnc,
+    mac: Hmac<Sha256>,
+    pending: Vec<u8>,
+    failed: Option<io::ErrorKind>,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    /// Writes the header of `encryptor` to `inner`, and returns a writer for the plain text.
+    pub fn new(encryptor: &Encryptor, mut inner: W) -> Result<EncryptWriter<W>> {
+        let cipher = Aes256CbcEnc::new_from_slices(encryptor.encryption_key().to_vec(),
+                                                   encryptor.iv().as_slice())
+            .map_err(|_| ErrorKind::EncryptionFailed(SymmetricCipherError::InvalidLength))?;
+        let mut mac = new_mac(encryptor.hmac_key())?;
+
+        let Header(ref header) = *encryptor.header();
+        inner.write_all(header)?;
+        mac.update(header);
+
+        Ok(EncryptWriter {
+            inner,
+            cipher,
+            mac,
+            pending: Vec::with_capacity(CHUNK_LEN + BLOCK_LEN),
+            failed: None,
+        })
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    fn check_not_failed(&self) -> io::Result<()> {
+        match self.failed {
+            Some(kind) => {
+                Err(io::Error::new(kind, "an earlier write to the underlying writer failed"))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Encrypts the first `len` bytes of `pending` and writes them. The cipher and the HMAC
+    /// being updated beforehand, a failed write leaves the `EncryptWriter` failed for good.
+    fn encrypt_pending(&mut self, len: usize) -> io::Result<()> {
+        for block in self.pending[..len].chunks_mut(BLOCK_LEN) {
+            self.cipher.encrypt_block_mut(GenericArray::from_mut_slice(block));
+        }
+        self.mac.update(&self.pending[..len]);
+        if let Err(e) = self.inner.write_all(&self.pending[..len]) {
+            self.failed = Some(e.kind());
+            return Err(e);
+        }
+        self.pending.drain(..len);
+        Ok(())
+    }
+
+    /// Pads and encrypts the last block, then writes the HMAC, returning the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.check_not_failed()?;
+        let pad = BLOCK_LEN - self.pending.len();
+        self.pending.extend(std::iter::repeat_n(pad as u8, pad));
+        self.encrypt_pending(BLOCK_LEN)?;
+
+        let hmac = self.mac.finalize().into_bytes();
+        self.inner.write_all(&hmac)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check_not_failed()?;
+        let len = buf.len().min(CHUNK_LEN);
+        self.pending.extend_from_slice(&buf[..len]);
+        let full_blocks = self.pending.len() / BLOCK_LEN * BLOCK_LEN;
+        if full_blocks > 0 {
+            self.encrypt_pending(full_blocks)?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The length of the decrypted `chunk` once its PKCS#7 padding is stripped.
+fn unpadded_len(chunk: &[u8]) -> Result<usize> {
+    let pad = chunk[chunk.len() - 1] as usize;
+    if pad == 0 || pad > BLOCK_LEN || chunk[chunk.len() - pad..].iter().any(|&b| b as usize != pad) {
+        return Err(ErrorKind::DecryptionFailed(SymmetricCipherError::InvalidPadding).into());
+    }
+    Ok(chunk.len() - pad)
+}
+
+/// A v3 `Message` read from a seekable stream, whose keys have been derived from its header.
+pub struct MessageReader<R> {
+    reader: R,
+    body_start: u64,
+    body_len: u64,
+    header: Header,
+    encryption_key: EncryptionKey,
+    hmac_key: HMACKey,
+    iv: IV,
+    verified_hmac: Option<Vec<u8>>,
+}
+
+impl<R: Read + Seek> MessageReader<R> {
+    /// Reads the header of the `Message` starting at the current position of `reader`, which
+    /// must extend up to the end of the stream.
+    pub fn new(credentials: &Credentials, mut reader: R) -> Result<MessageReader<R>> {
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
+
+        let header_len = credentials.header_len();
+        let len = end - start;
+        if len < (header_len + BLOCK_LEN + HMAC_LEN) as u64 {
+            return Err(Error::new(ErrorKind::NotEnoughInput(len as usize),
+                                  "Decryption failed, not enough input.".to_owned()));
+        }
+
+        let body_len = len - (header_len + HMAC_LEN) as u64;
+        if !body_len.is_multiple_of(BLOCK_LEN as u64) {
+            return Err(ErrorKind::DecryptionFailed(SymmetricCipherError::InvalidLength).into());
+        }
+
+        let mut header = vec![0; header_len];
+        reader.read_exact(&mut header)?;
+
+        let (encryption_key, hmac_key, iv) = match *credentials {
+            Credentials::Password(password) => {
+                let encryption_salt = Salt(header[2..10].to_vec());
+                let hmac_salt = Salt(header[10..18].to_vec());
+                (EncryptionKey::new(&encryption_salt, password.as_bytes()),
+                 HMACKey::new(&hmac_salt, password.as_bytes()),
+                 IV::from(header[18..34].to_vec()))
+            }
+            Credentials::Keys(ref ek, ref hk) => {
+                (ek.clone(), hk.clone(), IV::from(header[2..18].to_vec()))
+            }
+        };
+
+        Ok(MessageReader {
+            reader,
+            body_start: start + header_len as u64,
+            body_len,
+            header: Header(header),
+            encryption_key,
+            hmac_key,
+            iv,
+            verified_hmac: None,
+        })
+    }
+
+    /// An upper bound of the length of the plain text, which is shorter by 1 to 16 bytes of
+    /// padding.
+    pub fn max_plain_text_len(&self) -> u64 {
+        self.body_len
+    }
+
+    /// Reads the cipher text in chunks, feeding them to `f` and to a fresh HMAC, and returns the
+    /// computed HMAC along with the one embedded in the `Message`.
+    fn read_body<F>(&mut self, mut f: F) -> Result<(Vec<u8>, Vec<u8>)>
+        where F: FnMut(&mut [u8], bool) -> Result<()>
+    {
+        self.reader.seek(SeekFrom::Start(self.body_start))?;
+        let mut mac = new_mac(&self.hmac_key)?;
+        mac.update(&self.header.0);
+
+        let mut remaining = self.body_len;
+        let mut chunk = vec![0; CHUNK_LEN];
+        while remaining > 0 {
+            let len = remaining.min(CHUNK_LEN as u64) as usize;
+            self.reader.read_exact(&mut chunk[..len])?;
+            mac.update(&chunk[..len]);
+            remaining -= len as u64;
+            f(&mut chunk[..len], remaining == 0)?;
+        }
+
+        let mut hmac = vec![0; HMAC_LEN];
+        self.reader.read_exact(&mut hmac)?;
+        Ok((mac.finalize().into_bytes().to_vec(), hmac))
+    }
+
+    /// Validates the HMAC of the whole `Message`, without decrypting anything.
+    pub fn verify(&mut self) -> Result<()> {
+        if self.verified_hmac.is_some() {
+            return Ok(());
+        }
+
+        let (computed, embedded) = self.read_body(|_, _| Ok(()))?;
+        if !HMAC(embedded).is_equal_in_consistent_time_to(&HMAC(computed.clone())) {
+            return Err(Error::new(ErrorKind::HMACValidationFailed, "HMAC mismatch.".to_owned()));
+        }
+        self.verified_hmac = Some(computed);
+        Ok(())
+    }
+
+    /// Validates the HMAC (if `verify` wasn't called already), then decrypts the `Message` into
+    /// `writer`, returning the length of the plain text.
+    ///
+    /// Should the input change between the validation and the decryption, the HMAC computed
+    /// while decrypting won't match and `ErrorKind::HMACValidationFailed` is returned. All but
+    /// the last chunk of the plain text might have been written by then, so `writer` must be
+    /// discarded on error.
+    pub fn decrypt_to<W: Write>(&mut self, mut writer: W) -> Result<u64> {
+        self.verify()?;
+
+        let mut cipher = Aes256CbcDec::new_from_slices(self.encryption_key.to_vec(),
+                                                       self.iv.as_slice())
+            .map_err(|_| ErrorKind::DecryptionFailed(SymmetricCipherError::InvalidLength))?;
+
+        let mut written = 0;
+        // Held back until the second pass is known to match the first one.
+        let mut last_chunk = Vec::new();
+        let (computed, _) = self.read_body(|chunk, last| {
+            for block in chunk.chunks_mut(BLOCK_LEN) {
+                cipher.decrypt_block_mut(GenericArray::from_mut_slice(block));
+            }
+
+            if last {
+                last_chunk.extend_from_slice(&chunk[..unpadded_len(chunk)?]);
+            } else {
+                writer.write_all(chunk)?;
+                written += chunk.len() as u64;
+            }
+            Ok(())
+        })?;
+
+        check_unchanged(&self.verified_hmac, computed)?;
+        writer.write_all(&last_chunk)?;
+        writer.flush()?;
+        Ok(written + last_chunk.len() as u64)
+    }
+
+    /// Validates the HMAC (if `verify` wasn't called already), then turns the `Message` into a
+    /// `Read`er of its plain text, decrypting one chunk at a time.
+    pub fn into_plain_text(mut self) -> Result<DecryptReader<R>> {
+        self.verify()?;
+
+        let cipher = Aes256CbcDec::new_from_slices(self.encryption_key.to_vec(),
+                                                   self.iv.as_slice())
+            .map_err(|_| ErrorKind::DecryptionFailed(SymmetricCipherError::InvalidLength))?;
+        let mut mac = new_mac(&self.hmac_key)?;
+        mac.update(&self.header.0);
+        self.reader.seek(SeekFrom::Start(self.body_start))?;
+
+        Ok(DecryptReader {
+            remaining: self.body_len,
+            message: self,
+            cipher,
+            mac: Some(mac),
+            buffer: Vec::new(),
+            position: 0,
+            failed: false,
+        })
+    }
+}
+
+fn check_unchanged(verified_hmac: &Option<Vec<u8>>, computed: Vec<u8>) -> Result<()> {
+    match *verified_hmac {
+        Some(ref hmac) if HMAC(hmac.clone()).is_equal_in_consistent_time_to(&HMAC(computed)) => {
+            Ok(())
+        }
+        _ => {
+            Err(Error::new(ErrorKind::HMACValidationFailed,
+                           "The input changed while decrypting.".to_owned()))
+        }
+    }
+}
+
+/// The plain text of an authenticated `Message`, as returned by `MessageReader::into_plain_text`.
+///
+/// Decryption errors, and changes of the input since its validation (detected once the last
+/// chunk is read, which is only returned if it matches), are reported as
+/// `io::ErrorKind::InvalidData` errors, and so is every read after them. As with `decrypt_to`,
+/// whatever was read before an error must be discarded.
+pub struct DecryptReader<R> {
+    message: MessageReader<R>,
+    cipher: Aes256CbcDec,
+    mac: Option<Hmac<Sha256>>,
+    remaining: u64,
+    buffer: Vec<u8>,
+    position: usize,
+    failed: bool,
+}
+
+impl<R: Read + Seek> DecryptReader<R> {
+    fn fill_buffer(&mut self) -> Result<()> {
+        let len = self.remaining.min(CHUNK_LEN as u64) as usize;
+        self.buffer.resize(len, 0);
+        self.position = 0;
+        self.message.reader.read_exact(&mut self.buffer)?;
+        self.remaining -= len as u64;
+
+        let mut mac = self.mac.take().expect("reading past the end of the plain text");
+        mac.update(&self.buffer);
+        for block in self.buffer.chunks_mut(BLOCK_LEN) {
+            self.cipher.decrypt_block_mut(GenericArray::from_mut_slice(block));
+        }
+
+        if self.remaining == 0 {
+            let len = unpadded_len(&self.buffer)?;
+            self.buffer.truncate(len);
+            check_unchanged(&self.message.verified_hmac, mac.finalize().into_bytes().to_vec())?;
+        } else {
+            self.mac = Some(mac);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.buffer.len() {
+            if self.failed {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "decryption failed"));
+            }
+            if self.remaining == 0 {
+                return Ok(0);
+            }
+            if let Err(e) = self.fill_buffer() {
+                self.failed = true;
+                self.buffer.clear();
+                self.position = 0;
+                return Err(e.into());
+            }
+        }
+
+        let len = buf.len().min(self.buffer.len() - self.position);
+        buf[..len].copy_from_slice(&self.buffer[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// Encrypts everything read from `reader` into `writer`, drawing the salts and the `IV` from
+/// the given random number generator. Returns the underlying writer.
+pub fn encrypt_with_rng<R, W, G>(credentials: &Credentials,
+                                 mut reader: R,
+                                 writer: W,
+                                 rng: &mut G)
+                                 -> Result<W>
+    where R: Read,
+          W: Write,
+          G: RngCore + CryptoRng
+{
+    let encryptor = Encryptor::from_credentials_with_rng(credentials, rng)?;
+    let mut writer = EncryptWriter::new(&encryptor, writer)?;
+    io::copy(&mut reader, &mut writer)?;
+    writer.finish()
+}
+
+/// Encrypts everything read from `reader` into `writer`. Returns the underlying writer.
+pub fn encrypt<R: Read, W: Write>(credentials: &Credentials, reader: R, writer: W) -> Result<W> {
+    encrypt_with_rng(credentials, reader, writer, &mut OsRng)
+}
+
+/// Validates and then decrypts the `Message` read from `reader` into `writer`, returning the
+/// length of the plain text. Nothing is written if the HMAC validation fails, but `writer` must
+/// still be discarded on error, see `MessageReader::decrypt_to`.
+pub fn decrypt<R, W>(credentials: &Credentials, reader: R, writer: W) -> Result<u64>
+    where R: Read + Seek,
+          W: Write
+{
+    MessageReader::new(credentials, reader)?.decrypt_to(writer)
+}
@@ -0,0 +1,137 @@
+//! Short-lived, opaque tokens (password resets, download links...), in the spirit of Fernet.
+//!
+//! A token is a key-based v3 `Message`, in URL-safe base64 without padding. Its plain text is
+//! `"RNCT" | version (1) | issued at (u64, BE) | time to live (u64, BE) | payload`, with times in
+//! seconds since the Unix epoch and a time to live of 0 meaning "never expires": as the HMAC
+//! covers them, they can't be tampered with.
+//!
+//! Decoding checks the times against a `Clock` (`SystemClock`, or any `Fn() -> u64` in tests),
+//! tolerating `MAX_CLOCK_SKEW` seconds of drift between the issuer and the verifier.
+
+extern crate base64;
+
+use core::num::NonZeroU64;
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use self::base64::Engine;
+use self::base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+use v3::encryptor::Encryptor;
+use v3::decryptor::Decryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::keys::KeyPair;
+use v3::rng::{RngCore, CryptoRng};
+#[cfg(feature = "std")]
+use v3::rng::OsRng;
+
+/// The magic bytes starting the plain text of every token.
+pub const MAGIC: &[u8; 4] = b"RNCT";
+/// The version of the token format.
+pub const VERSION: u8 = 1;
+/// How far in the future (in seconds) a token may have been issued, to allow for clock drift.
+pub const MAX_CLOCK_SKEW: u64 = 60;
+
+const PREFIX_LEN: usize = 4 + 1 + 8 + 8;
+
+/// A source of the current time, in seconds since the Unix epoch.
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+impl<F: Fn() -> u64> Clock for F {
+    fn now(&self) -> u64 {
+        self()
+    }
+}
+
+/// The system clock.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+    }
+}
+
+fn invalid_format(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidFormat, message.to_owned())
+}
+
+/// Issues a token carrying `payload`, valid for `ttl` seconds (forever if `None`) from the time
+/// given by `clock`, drawing the `IV` from the given random number generator.
+pub fn encode_with_rng<C, R>(keys: &KeyPair,
+                             payload: &[u8],
+                             ttl: Option<NonZeroU64>,
+                             clock: &C,
+                             rng: &mut R)
+                             -> Result<String>
+    where C: Clock + ?Sized,
+          R: RngCore + CryptoRng
+{
+    let mut plain_text = Vec::with_capacity(PREFIX_LEN + payload.len());
+    plain_text.extend_from_slice(MAGIC);
+    plain_text.push(VERSION);
+    plain_text.extend_from_slice(&clock.now().to_be_bytes());
+    plain_text.extend_from_slice(&ttl.map_or(0, NonZeroU64::get).to_be_bytes());
+    plain_text.extend_from_slice(payload);
+
+    let message = Encryptor::from_keys_with_rng(keys.encryption_key(), keys.hmac_key(), rng)?
+        .encrypt(&plain_text)?;
+    Ok(URL_SAFE_NO_PAD.encode(message))
+}
+
+/// Issues a token carrying `payload`, valid for `ttl` seconds (forever if `None`) from now.
+#[cfg(feature = "std")]
+pub fn encode(keys: &KeyPair, payload: &[u8], ttl: Option<NonZeroU64>) -> Result<String> {
+    encode_with_rng(keys, payload, ttl, &SystemClock, &mut OsRng)
+}
+
+/// Checks a token issued by `encode` against the time given by `clock`, returning its payload.
+///
+/// Fails with `ErrorKind::TokenExpired` once its time to live has elapsed, and with
+/// `ErrorKind::TokenNotYetValid` if it was issued more than `MAX_CLOCK_SKEW` seconds in the
+/// future. Both are only reported for authentic tokens.
+pub fn decode_with_clock<C: Clock + ?Sized>(keys: &KeyPair,
+                                            token: &str,
+                                            clock: &C)
+                                            -> Result<Vec<u8>> {
+    let message = URL_SAFE_NO_PAD.decode(token.trim())
+        .map_err(|_| invalid_format("Invalid URL-safe base64."))?;
+    let plain_text = Decryptor::from_keys(keys.encryption_key(), keys.hmac_key(), &message)?
+        .decrypt(&message)?;
+    if plain_text.len() < PREFIX_LEN || &plain_text[..4] != MAGIC {
+        return Err(invalid_format("The message isn't a token."));
+    }
+    if plain_text[4] != VERSION {
+        return Err(invalid_format("Unsupported token version."));
+    }
+
+    let mut field = [0; 8];
+    field.copy_from_slice(&plain_text[5..13]);
+    let issued_at = u64::from_be_bytes(field);
+    field.copy_from_slice(&plain_text[13..PREFIX_LEN]);
+    let ttl = u64::from_be_bytes(field);
+
+    let now = clock.now();
+    if issued_at > now.saturating_add(MAX_CLOCK_SKEW) {
+        return Err(Error::new(ErrorKind::TokenNotYetValid,
+                              "The token was issued in the future.".to_owned()));
+    }
+    if ttl != 0 && now >= issued_at.saturating_add(ttl) {
+        return Err(Error::new(ErrorKind::TokenExpired, "The token has expired.".to_owned()));
+    }
+    Ok(plain_text[PREFIX_LEN..].to_vec())
+}
+
+/// Checks a token issued by `encode` against the system clock, returning its payload.
+#[cfg(feature = "std")]
+pub fn decode(keys: &KeyPair, token: &str) -> Result<Vec<u8>> {
+    decode_with_clock(keys, token, &SystemClock)
+}
@@ -1,24 +1,30 @@
+extern crate hmac;
+extern crate sha2;
 
-extern crate rand;
-extern crate crypto;
-extern crate fastpbkdf2;
-
-use self::crypto::mac::Mac;
-use self::fastpbkdf2::pbkdf2_hmac_sha1;
-use std::iter::repeat;
-use self::crypto::hmac::Hmac;
-use self::crypto::sha2::Sha256;
-use self::rand::{Rng, OsRng};
-use std::fmt::{Display, Formatter, Result as FmtResult};
-use std::result::Result as StdResult;
-use std;
+use self::hmac::{Hmac, Mac};
+use self::sha2::Sha256;
+use core::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use core::result::Result as StdResult;
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
 
 use v3::errors::{Result, Error, ErrorKind};
+use v3::kdf;
+use v3::rng::{RngCore, CryptoRng, RngError};
+#[cfg(feature = "std")]
+use v3::rng::OsRng;
 
 /// An `EncryptionKey`, which can be constructed from a `EncryptionSalt` and a password.
-#[derive (Clone, Debug)]
+#[derive (Clone)]
 pub struct EncryptionKey(Vec<u8>);
 
+/// Keys are never printed, only their length.
+impl Debug for EncryptionKey {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "EncryptionKey(<{} bytes redacted>)", self.0.len())
+    }
+}
+
 impl<'a> EncryptionKey {
     /// Creates a new `EncryptionKey` out of an `EncryptionSalt` and a password.
     pub fn new(encryption_salt: &EncryptionSalt, password: &'a [u8]) -> EncryptionKey {
@@ -41,8 +47,14 @@ pub struct Salt(pub Vec<u8>);
 
 impl Salt {
     /// Creates a new, completely random `Salt` of 8 bytes.
+    #[cfg(feature = "std")]
     pub fn new() -> Result<Salt> {
-        match random_data_of_len(8) {
+        Salt::new_with_rng(&mut OsRng)
+    }
+
+    /// Creates a new `Salt` of 8 bytes, drawn from the given random number generator.
+    pub fn new_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> Result<Salt> {
+        match random_data_of_len(rng, 8) {
             Err(e) => {
                 Err(Error::new(ErrorKind::SaltGenerationFailed(e),
                                "Salt Generation failed.".to_owned()))
@@ -59,15 +71,18 @@ impl Salt {
 }
 
 /// A `HMACKey`, which can be constructed from an `HMACSalt` and a password.
-#[derive (Clone, Debug, PartialEq, Eq)]
+#[derive (Clone, PartialEq, Eq)]
 pub struct HMACKey(Vec<u8>);
 
-fn new_key_with_salt<'a>(salt: &Salt, password: &'a [u8]) -> Vec<u8> {
-    let Salt(ref salt) = *salt;
-    let mut result: Vec<u8> = repeat(0).take(32).collect();
-    let mut password_mut = password.clone();
-    pbkdf2_hmac_sha1(&mut password_mut, &salt[..], 10_000, &mut result[..]);
-    result
+/// Keys are never printed, only their length.
+impl Debug for HMACKey {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "HMACKey(<{} bytes redacted>)", self.0.len())
+    }
+}
+
+fn new_key_with_salt(salt: &Salt, password: &[u8]) -> Vec<u8> {
+    kdf::derive_key(password, salt).to_vec()
 }
 
 impl<'a> HMACKey {
@@ -78,6 +93,11 @@ impl<'a> HMACKey {
     pub fn from(raw_key: Vec<u8>) -> HMACKey {
         HMACKey(raw_key)
     }
+
+    pub fn to_vec(&self) -> &Vec<u8> {
+        let HMACKey(ref v) = *self;
+        v
+    }
 }
 
 /// A RNCryptor `Header` built during the encryption/decryption process.
@@ -104,14 +124,25 @@ pub type PlainText = [u8];
 /// An encrypted message, the result of the encryption process.
 pub type Message = Vec<u8>;
 
-fn random_data_of_len(size: usize) -> StdResult<Vec<u8>, std::io::Error> {
-    Ok(try!(OsRng::new().map(|mut gen| gen.gen_iter().take(size).collect::<Vec<u8>>())))
+fn random_data_of_len<R: RngCore + CryptoRng>(rng: &mut R,
+                                               size: usize)
+                                               -> StdResult<Vec<u8>, RngError> {
+    let mut data = vec![0; size];
+    rng.try_fill_bytes(&mut data)?;
+    Ok(data)
 }
 
 impl IV {
     /// Creates a new, completely random `IV` (Initialization Vector) of 16 bytes.
+    #[cfg(feature = "std")]
     pub fn new() -> Result<IV> {
-        match random_data_of_len(16) {
+        IV::new_with_rng(&mut OsRng)
+    }
+
+    /// Creates a new `IV` (Initialization Vector) of 16 bytes, drawn from the given random
+    /// number generator.
+    pub fn new_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> Result<IV> {
+        match random_data_of_len(rng, 16) {
             Err(e) => {
                 Err(Error::new(ErrorKind::IVGenerationFailed(e),
                                "IV Generation failed.".to_owned()))
@@ -148,19 +179,60 @@ pub struct CipherText(pub Vec<u8>);
 pub struct HMAC(pub Vec<u8>);
 
 impl HMAC {
-    pub fn new(&Header(ref h): &Header, txt: &[u8], &HMACKey(ref key): &HMACKey) -> Result<HMAC> {
-        let mut input = Vec::new();
-        input.extend(h);
-        input.extend(txt);
+    pub fn new(header: &Header, txt: &[u8], key: &HMACKey) -> Result<HMAC> {
+        HMAC::compute(header, txt, None, key)
+    }
+
+    /// Like `new`, but also authenticates `associated_data`, followed by its length (u64, BE)
+    /// so that it can't be confused with the end of `txt`. **Not part of the v3 spec.**
+    pub fn with_associated_data(header: &Header,
+                                txt: &[u8],
+                                associated_data: &[u8],
+                                key: &HMACKey)
+                                -> Result<HMAC> {
+        HMAC::compute(header, txt, Some(associated_data), key)
+    }
 
-        let mut hmac = Hmac::new(Sha256::new(), key);
-        hmac.input(&input);
-        Ok(HMAC(Vec::from(hmac.result().code())))
+    fn compute(header: &Header,
+               txt: &[u8],
+               associated_data: Option<&[u8]>,
+               key: &HMACKey)
+               -> Result<HMAC> {
+        let mut hmac = Hmac::<Sha256>::new_from_slice(&key.0).map_err(|_| {
+                Error::new(ErrorKind::HMACGenerationFailed,
+                           "HMAC Generation failed.".to_owned())
+            })?;
+        hmac.update(&header.0);
+        hmac.update(txt);
+        if let Some(associated_data) = associated_data {
+            hmac.update(associated_data);
+            hmac.update(&(associated_data.len() as u64).to_be_bytes());
+        }
+        Ok(HMAC(hmac.finalize().into_bytes().to_vec()))
     }
 
     pub fn is_equal_in_consistent_time_to(&self, &HMAC(ref other): &HMAC) -> bool {
         let HMAC(ref this) = *self;
-        this.iter().zip(other.iter()).fold(true, |acc, (x, y)| acc && (x == y))
+        this.len() == other.len() &&
+        this.iter().zip(other.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+    }
+}
+
+/// The secrets a `Message` is encrypted with: either a password, for the password-based API,
+/// or an `EncryptionKey` and an `HMACKey`, for the key-based API.
+#[derive(Clone, Debug)]
+pub enum Credentials<'a> {
+    Password(&'a str),
+    Keys(EncryptionKey, HMACKey),
+}
+
+impl<'a> Credentials<'a> {
+    /// The length of the header of a `Message` encrypted with these credentials.
+    pub fn header_len(&self) -> usize {
+        match *self {
+            Credentials::Password(_) => 34,
+            Credentials::Keys(_, _) => 18,
+        }
     }
 }
 
@@ -0,0 +1,318 @@
+//! Single-file vaults of named secrets, behind a single password.
+//!
+//! Each secret is its own key-based v3 `Message`, and so is the index listing their names. The
+//! keys are derived once from the password and the salts stored in the file (with
+//! `kdf::derive_keys`, exactly like a password-based `Message` would), so that opening a vault
+//! costs a single pair of PBKDF2 runs however many secrets it holds. The layout is:
+//!
+//! ```text
+//! "RNCV" | version (1) | encryption salt (8) | HMAC salt (8) | index length (u32, BE) | index
+//!        | entry*
+//! ```
+//!
+//! where the index decrypts to `count (u32, BE) | (name length (u16, BE) | name | entry length
+//! (u32, BE) | entry HMAC (32))*`, giving the entries in the order they follow. The HMAC of every
+//! entry being recorded in the (authenticated) index, entries can't be swapped, rolled back or
+//! dropped independently of it.
+//!
+//! Secrets stay encrypted in memory until `get` decrypts them. `save` writes through a temporary
+//! file renamed over the vault, while holding an exclusive lock on a `<vault>.lock` file next to
+//! it, which `open` holds shared while reading: readers never see a half-written vault, and
+//! writers don't interleave. Writers still don't see each other's changes, though: of two
+//! processes opening, updating and saving the same vault, the last one to save wins.
+
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::string::String;
+use std::vec::Vec;
+use std::borrow::ToOwned;
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::decryptor::Decryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::kdf::derive_keys;
+use v3::atomic_file;
+use v3::rng::{RngCore, CryptoRng, OsRng};
+
+/// The magic bytes starting every vault file.
+pub const MAGIC: &[u8; 4] = b"RNCV";
+/// The version of the vault format.
+pub const VERSION: u8 = 1;
+
+const SALT_LEN: usize = 8;
+const HEADER_LEN: usize = 5 + 2 * SALT_LEN + 4;
+const HMAC_LEN: usize = 32;
+
+/// Named secrets, sealed with the keys derived from a password.
+#[derive(Clone)]
+pub struct Vault {
+    encryption_salt: EncryptionSalt,
+    hmac_salt: HMACSalt,
+    encryption_key: EncryptionKey,
+    hmac_key: HMACKey,
+    entries: BTreeMap<String, Message>,
+}
+
+fn invalid_format(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidFormat, message.to_owned())
+}
+
+fn truncated(len: usize) -> Error {
+    Error::new(ErrorKind::NotEnoughInput(len), "Truncated vault.".to_owned())
+}
+
+/// Takes the next `len` bytes of `bytes`, from `position` on.
+fn take<'a>(bytes: &'a [u8], position: &mut usize, len: usize) -> Result<&'a [u8]> {
+    if bytes.len() - *position < len {
+        return Err(truncated(bytes.len()));
+    }
+    *position += len;
+    Ok(&bytes[*position - len..*position])
+}
+
+fn take_u16(bytes: &[u8], position: &mut usize) -> Result<usize> {
+    let taken = take(bytes, position, 2)?;
+    Ok(u16::from_be_bytes([taken[0], taken[1]]) as usize)
+}
+
+fn take_u32(bytes: &[u8], position: &mut usize) -> Result<usize> {
+    let taken = take(bytes, position, 4)?;
+    Ok(u32::from_be_bytes([taken[0], taken[1], taken[2], taken[3]]) as usize)
+}
+
+/// Opens (creating it if needed) the lock file of the vault at `path`.
+fn lock_file(path: &Path) -> Result<File> {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    Ok(OpenOptions::new().create(true).truncate(false).write(true).open(PathBuf::from(lock_path))?)
+}
+
+impl Vault {
+    /// Creates an empty vault for `password`, drawing the salts from the given random number
+    /// generator.
+    pub fn create_with_rng<R: RngCore + CryptoRng>(password: &str, rng: &mut R) -> Result<Vault> {
+        let encryption_salt = Salt::new_with_rng(rng)?;
+        let hmac_salt = Salt::new_with_rng(rng)?;
+        Vault::with_salts(password, encryption_salt, hmac_salt, BTreeMap::new())
+    }
+
+    /// Creates an empty vault for `password`.
+    pub fn create(password: &str) -> Result<Vault> {
+        Vault::create_with_rng(password, &mut OsRng)
+    }
+
+    fn with_salts(password: &str,
+                  encryption_salt: EncryptionSalt,
+                  hmac_salt: HMACSalt,
+                  entries: BTreeMap<String, Message>)
+                  -> Result<Vault> {
+        if password.is_empty() {
+            return Err(Error::new(ErrorKind::WrongInputSize(0),
+                                  "Password length cannot be <= 0.".to_owned()));
+        }
+        let (encryption_key, hmac_key) = derive_keys(password.as_bytes(),
+                                                     &encryption_salt,
+                                                     &hmac_salt);
+        Ok(Vault {
+            encryption_salt,
+            hmac_salt,
+            encryption_key,
+            hmac_key,
+            entries,
+        })
+    }
+
+    fn credentials(&self) -> Credentials<'_> {
+        Credentials::Keys(self.encryption_key.clone(), self.hmac_key.clone())
+    }
+
+    /// The names of the secrets, sorted.
+    pub fn list(&self) -> Vec<&str> {
+        self.entries.keys().map(|name| name.as_str()).collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Encrypts `secret` under `name`, replacing any secret of the same name, drawing the `IV`
+    /// from the given random number generator. Returns whether a secret was replaced.
+    pub fn add_with_rng<R: RngCore + CryptoRng>(&mut self,
+                                                name: &str,
+                                                secret: &PlainText,
+                                                rng: &mut R)
+                                                -> Result<bool> {
+        if name.is_empty() || name.len() > u16::MAX as usize {
+            return Err(Error::new(ErrorKind::WrongInputSize(name.len()),
+                                  "Names must be between 1 and 65535 bytes long.".to_owned()));
+        }
+        let message = Encryptor::from_credentials_with_rng(&self.credentials(), rng)?
+            .encrypt(secret)?;
+        Ok(self.entries.insert(name.to_owned(), message).is_some())
+    }
+
+    /// Encrypts `secret` under `name`, replacing any secret of the same name. Returns whether a
+    /// secret was replaced.
+    pub fn add(&mut self, name: &str, secret: &PlainText) -> Result<bool> {
+        self.add_with_rng(name, secret, &mut OsRng)
+    }
+
+    /// Decrypts the secret stored under `name`, if any.
+    pub fn get(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        match self.entries.get(name) {
+            Some(message) => {
+                let credentials = self.credentials();
+                Ok(Some(Decryptor::from_credentials(&credentials, message)?.decrypt(message)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Removes the secret stored under `name`, returning whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    /// Re-encrypts every secret with the keys derived from `new_password` and fresh salts,
+    /// drawing them and the `IV`s from the given random number generator. The vault being
+    /// already open, the current password isn't needed.
+    pub fn change_password_with_rng<R: RngCore + CryptoRng>(&mut self,
+                                                            new_password: &str,
+                                                            rng: &mut R)
+                                                            -> Result<()> {
+        let mut changed = Vault::with_salts(new_password,
+                                            Salt::new_with_rng(rng)?,
+                                            Salt::new_with_rng(rng)?,
+                                            BTreeMap::new())?;
+        for name in self.entries.keys() {
+            let secret = self.get(name)?.unwrap_or_default();
+            changed.add_with_rng(name, &secret, rng)?;
+        }
+        *self = changed;
+        Ok(())
+    }
+
+    /// Re-encrypts every secret with the keys derived from `new_password` and fresh salts.
+    pub fn change_password(&mut self, new_password: &str) -> Result<()> {
+        self.change_password_with_rng(new_password, &mut OsRng)
+    }
+
+    /// Serialises the vault, drawing the `IV` of the index from the given random number
+    /// generator.
+    pub fn to_bytes_with_rng<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Result<Vec<u8>> {
+        if self.entries.len() > u32::MAX as usize {
+            return Err(Error::new(ErrorKind::LimitExceeded(u32::MAX as usize),
+                                  "Too many secrets.".to_owned()));
+        }
+        let mut index = Vec::new();
+        index.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+        for (name, message) in &self.entries {
+            index.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            index.extend_from_slice(name.as_bytes());
+            index.extend_from_slice(&(message.len() as u32).to_be_bytes());
+            index.extend_from_slice(&message[message.len() - HMAC_LEN..]);
+        }
+        let index = Encryptor::from_credentials_with_rng(&self.credentials(), rng)?
+            .encrypt(&index)?;
+
+        let entries_len = self.entries.values().map(|m| m.len()).sum::<usize>();
+        let mut bytes = Vec::with_capacity(HEADER_LEN + index.len() + entries_len);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(self.encryption_salt.as_slice());
+        bytes.extend_from_slice(self.hmac_salt.as_slice());
+        bytes.extend_from_slice(&(index.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&index);
+        for message in self.entries.values() {
+            bytes.extend_from_slice(message);
+        }
+        Ok(bytes)
+    }
+
+    /// Serialises the vault.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        self.to_bytes_with_rng(&mut OsRng)
+    }
+
+    /// Opens a serialised vault with `password`, authenticating its index and checking every
+    /// entry against it (but without decrypting the secrets).
+    pub fn from_bytes(bytes: &[u8], password: &str) -> Result<Vault> {
+        if bytes.len() < 5 || &bytes[..4] != MAGIC {
+            return Err(invalid_format("Not a vault."));
+        }
+        if bytes[4] != VERSION {
+            return Err(invalid_format("Unsupported vault version."));
+        }
+
+        let mut position = 5;
+        let encryption_salt = Salt(take(bytes, &mut position, SALT_LEN)?.to_vec());
+        let hmac_salt = Salt(take(bytes, &mut position, SALT_LEN)?.to_vec());
+        let mut vault = Vault::with_salts(password, encryption_salt, hmac_salt, BTreeMap::new())?;
+
+        let index_len = take_u32(bytes, &mut position)?;
+        let index = take(bytes, &mut position, index_len)?;
+        let index = Decryptor::from_credentials(&vault.credentials(), index)?.decrypt(index)?;
+
+        let mut index_position = 0;
+        let count = take_u32(&index, &mut index_position)?;
+        for _ in 0..count {
+            let name_len = take_u16(&index, &mut index_position)?;
+            let name = take(&index, &mut index_position, name_len)?;
+            let name = String::from_utf8(name.to_vec())
+                .map_err(|_| invalid_format("Invalid secret name."))?;
+            let message_len = take_u32(&index, &mut index_position)?;
+            let hmac = HMAC(take(&index, &mut index_position, HMAC_LEN)?.to_vec());
+
+            let message = take(bytes, &mut position, message_len)?;
+            if message_len < HMAC_LEN ||
+               !hmac.is_equal_in_consistent_time_to(&HMAC(message[message_len - HMAC_LEN..]
+                                                               .to_vec())) {
+                return Err(Error::new(ErrorKind::HMACValidationFailed,
+                                      "A secret doesn't match the vault index.".to_owned()));
+            }
+            vault.entries.insert(name, message.to_vec());
+        }
+        if position != bytes.len() || index_position != index.len() {
+            return Err(invalid_format("Trailing bytes after the last secret."));
+        }
+        Ok(vault)
+    }
+
+    /// Reads and opens the vault at `path`, holding a shared lock while reading.
+    pub fn open<P: AsRef<Path>>(path: P, password: &str) -> Result<Vault> {
+        let path = path.as_ref();
+        let lock = lock_file(path)?;
+        lock.lock_shared()?;
+        let bytes = fs::read(path)?;
+        drop(lock);
+        Vault::from_bytes(&bytes, password)
+    }
+
+    /// Writes the vault to `path`, through a synced temporary file renamed over it while holding
+    /// an exclusive lock, so that the vault is never seen half-written, even after a crash.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let bytes = self.to_bytes()?;
+
+        let lock = lock_file(path)?;
+        lock.lock()?;
+        atomic_file::write(path, &bytes)
+    }
+}
+
+impl Debug for Vault {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.debug_struct("Vault").field("names", &self.list()).finish_non_exhaustive()
+    }
+}
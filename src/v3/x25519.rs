@@ -0,0 +1,260 @@
+//! Encryption to X25519 public keys, in the spirit of age: no password is shared, and the
+//! payload is still a regular key-based v3 `Message`.
+//!
+//! The payload is encrypted with random keys (a `KeyPair`), which are then wrapped for each
+//! recipient in a "stanza": an ephemeral X25519 public key, and the 64 bytes of key material
+//! sealed in a key-based v3 `Message` under keys derived (`kdf::hkdf_keys_with_salt`) from the
+//! shared secret, salted with the ephemeral and the recipient public keys. The layout is:
+//!
+//! ```text
+//! "RNCX" | version (1) | stanza count (1) | (ephemeral key (32) | wrapped keys (130))* | payload
+//! ```
+//!
+//! Stanzas don't say which recipient they are for: decrypting tries each of them, which costs
+//! one X25519 operation and one HMAC validation per stanza.
+//!
+//! Identities (the private keys) and recipients (the public keys) are serialized as single
+//! lines, `rncryptor-x25519-identity:v1:` or `rncryptor-x25519-recipient:v1:` followed by the
+//! 32 bytes of the key in standard, padded base64.
+
+extern crate base64;
+extern crate x25519_dalek;
+
+use core::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use core::str::FromStr;
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+
+use self::base64::Engine;
+use self::base64::engine::general_purpose::STANDARD;
+use self::x25519_dalek::{PublicKey, StaticSecret};
+
+use v3::types::*;
+use v3::encryptor::Encryptor;
+use v3::decryptor::Decryptor;
+use v3::errors::{Result, Error, ErrorKind};
+use v3::kdf::hkdf_keys_with_salt;
+use v3::keys::KeyPair;
+use v3::rng::{RngCore, CryptoRng};
+#[cfg(feature = "std")]
+use v3::rng::OsRng;
+
+/// The magic bytes starting every message encrypted to X25519 recipients.
+pub const MAGIC: &[u8; 4] = b"RNCX";
+/// The version of the format.
+pub const VERSION: u8 = 1;
+/// The maximum number of recipients of a message.
+pub const MAX_RECIPIENTS: usize = u8::MAX as usize;
+/// The prefix of the current identity format.
+pub const IDENTITY_PREFIX: &str = "rncryptor-x25519-identity:v1:";
+/// The prefix of the current recipient format.
+pub const RECIPIENT_PREFIX: &str = "rncryptor-x25519-recipient:v1:";
+
+const KEY_LEN: usize = 32;
+/// An ephemeral public key, then the 64 bytes of key material in a key-based message.
+const STANZA_LEN: usize = KEY_LEN + 2 + 16 + 80 + 32;
+const HKDF_INFO: &[u8] = b"rncryptor-x25519:v1";
+
+fn invalid_format(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidFormat, message.to_owned())
+}
+
+fn decode_key(input: &str, prefix: &str, what: &str) -> Result<[u8; KEY_LEN]> {
+    let encoded = input.trim().strip_prefix(prefix).ok_or_else(|| {
+        invalid_format(&format!("Not an X25519 {}, or an unsupported version.", what))
+    })?;
+    let bytes = STANDARD.decode(encoded).map_err(|_| invalid_format("Invalid base64."))?;
+    if bytes.len() != KEY_LEN {
+        return Err(Error::new(ErrorKind::WrongInputSize(bytes.len()),
+                              format!("X25519 keys must be {} bytes long.", KEY_LEN)));
+    }
+    let mut key = [0; KEY_LEN];
+    key.copy_from_slice(&bytes);
+    Ok(key)
+}
+
+/// The keys wrapping the payload keys for `recipient`, given the shared secret.
+fn wrapping_keys(shared_secret: &[u8; KEY_LEN],
+                 ephemeral: &PublicKey,
+                 recipient: &PublicKey)
+                 -> Result<(EncryptionKey, HMACKey)> {
+    let mut salt = [0; 2 * KEY_LEN];
+    salt[..KEY_LEN].copy_from_slice(ephemeral.as_bytes());
+    salt[KEY_LEN..].copy_from_slice(recipient.as_bytes());
+    hkdf_keys_with_salt(shared_secret, &salt, HKDF_INFO)
+}
+
+/// An X25519 public key, which messages can be encrypted to.
+///
+/// `Display` and `FromStr` use the recipient format described in the module documentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Recipient(PublicKey);
+
+impl Recipient {
+    pub fn from_bytes(bytes: [u8; KEY_LEN]) -> Recipient {
+        Recipient(PublicKey::from(bytes))
+    }
+
+    pub fn to_bytes(&self) -> [u8; KEY_LEN] {
+        self.0.to_bytes()
+    }
+}
+
+impl Display for Recipient {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}{}", RECIPIENT_PREFIX, STANDARD.encode(self.0.as_bytes()))
+    }
+}
+
+impl FromStr for Recipient {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Recipient> {
+        decode_key(s, RECIPIENT_PREFIX, "recipient").map(Recipient::from_bytes)
+    }
+}
+
+/// An X25519 private key, which decrypts the messages encrypted to its `recipient`.
+///
+/// `Display` and `FromStr` use the identity format described in the module documentation.
+/// `Debug` never prints the key.
+#[derive(Clone)]
+pub struct Identity(StaticSecret);
+
+impl Identity {
+    /// Generates a fresh `Identity`, drawn from the given random number generator.
+    pub fn generate_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> Result<Identity> {
+        let mut bytes = [0; KEY_LEN];
+        rng.try_fill_bytes(&mut bytes).map_err(|e| {
+            Error::new(ErrorKind::KeyGenerationFailed(e), "Key generation failed.".to_owned())
+        })?;
+        Ok(Identity::from_bytes(bytes))
+    }
+
+    /// Generates a fresh, completely random `Identity`.
+    #[cfg(feature = "std")]
+    pub fn generate() -> Result<Identity> {
+        Identity::generate_with_rng(&mut OsRng)
+    }
+
+    pub fn from_bytes(bytes: [u8; KEY_LEN]) -> Identity {
+        Identity(StaticSecret::from(bytes))
+    }
+
+    pub fn to_bytes(&self) -> [u8; KEY_LEN] {
+        self.0.to_bytes()
+    }
+
+    /// The public key matching this identity, to be handed out to senders.
+    pub fn recipient(&self) -> Recipient {
+        Recipient(PublicKey::from(&self.0))
+    }
+
+    /// Unwraps the payload keys from `stanza`, if it was made for this identity.
+    fn unwrap(&self, stanza: &[u8]) -> Result<KeyPair> {
+        let mut ephemeral = [0; KEY_LEN];
+        ephemeral.copy_from_slice(&stanza[..KEY_LEN]);
+        let ephemeral = PublicKey::from(ephemeral);
+        let shared_secret = self.0.diffie_hellman(&ephemeral);
+        if !shared_secret.was_contributory() {
+            return Err(invalid_format("Invalid ephemeral X25519 key."));
+        }
+
+        let (encryption_key, hmac_key) =
+            wrapping_keys(shared_secret.as_bytes(), &ephemeral, &self.recipient().0)?;
+        let wrapped = &stanza[KEY_LEN..];
+        KeyPair::from_bytes(&Decryptor::from_keys(encryption_key, hmac_key, wrapped)?
+            .decrypt(wrapped)?)
+    }
+}
+
+impl Display for Identity {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}{}", IDENTITY_PREFIX, STANDARD.encode(self.0.as_bytes()))
+    }
+}
+
+impl FromStr for Identity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Identity> {
+        decode_key(s, IDENTITY_PREFIX, "identity").map(Identity::from_bytes)
+    }
+}
+
+impl Debug for Identity {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.write_str("Identity(<redacted>)")
+    }
+}
+
+/// Encrypts `plain_text` to each of `recipients`, drawing the keys and the `IV`s from the given
+/// random number generator.
+pub fn encrypt_with_rng<R: RngCore + CryptoRng>(recipients: &[Recipient],
+                                                plain_text: &PlainText,
+                                                rng: &mut R)
+                                                -> Result<Vec<u8>> {
+    if recipients.is_empty() || recipients.len() > MAX_RECIPIENTS {
+        return Err(Error::new(ErrorKind::WrongInputSize(recipients.len()),
+                              "Between 1 and 255 recipients are needed.".to_owned()));
+    }
+
+    let keys = KeyPair::generate_with_rng(rng)?;
+    let payload = Encryptor::from_keys_with_rng(keys.encryption_key(), keys.hmac_key(), rng)?
+        .encrypt(plain_text)?;
+
+    let mut bytes = Vec::with_capacity(6 + recipients.len() * STANZA_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    bytes.push(recipients.len() as u8);
+    for recipient in recipients {
+        let ephemeral_secret = Identity::generate_with_rng(rng)?.0;
+        let ephemeral = PublicKey::from(&ephemeral_secret);
+        let shared_secret = ephemeral_secret.diffie_hellman(&recipient.0);
+        if !shared_secret.was_contributory() {
+            return Err(invalid_format("Invalid X25519 recipient."));
+        }
+
+        let (encryption_key, hmac_key) =
+            wrapping_keys(shared_secret.as_bytes(), &ephemeral, &recipient.0)?;
+        let wrapped = Encryptor::from_keys_with_rng(encryption_key, hmac_key, rng)?
+            .encrypt(&keys.to_bytes())?;
+        bytes.extend_from_slice(ephemeral.as_bytes());
+        bytes.extend_from_slice(&wrapped);
+    }
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Encrypts `plain_text` to each of `recipients`.
+#[cfg(feature = "std")]
+pub fn encrypt(recipients: &[Recipient], plain_text: &PlainText) -> Result<Vec<u8>> {
+    encrypt_with_rng(recipients, plain_text, &mut OsRng)
+}
+
+/// Decrypts a message produced by `x25519::encrypt` with one of its recipients' `identity`.
+pub fn decrypt(identity: &Identity, message: &[u8]) -> Result<Vec<u8>> {
+    if message.len() < 6 || &message[..4] != MAGIC {
+        return Err(invalid_format("Not a message encrypted to X25519 recipients."));
+    }
+    if message[4] != VERSION {
+        return Err(invalid_format("Unsupported X25519 message version."));
+    }
+
+    let payload_start = 6 + message[5] as usize * STANZA_LEN;
+    if message.len() < payload_start {
+        return Err(Error::new(ErrorKind::NotEnoughInput(message.len()),
+                              "Truncated X25519 message.".to_owned()));
+    }
+
+    let keys = message[6..payload_start]
+        .chunks(STANZA_LEN)
+        .filter_map(|stanza| identity.unwrap(stanza).ok())
+        .next()
+        .ok_or_else(|| {
+            Error::new(ErrorKind::HMACValidationFailed,
+                       "The message wasn't encrypted to this identity.".to_owned())
+        })?;
+    let payload = &message[payload_start..];
+    Decryptor::from_keys(keys.encryption_key(), keys.hmac_key(), payload)?.decrypt(payload)
+}
@@ -0,0 +1,47 @@
+extern crate rncryptor;
+
+#[macro_use]
+mod common;
+
+use rncryptor::v3;
+use rncryptor::v3::aad;
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::keys::KeyPair;
+use rncryptor::v3::rng::seeded_rng;
+use rncryptor::v3::types::Credentials;
+
+#[test]
+fn the_associated_data_must_match() {
+    let keys = KeyPair::generate_with_rng(&mut seeded_rng(1)).unwrap();
+    let credentials = keys.credentials();
+    let message = aad::encrypt_with_rng(&credentials, b"record", b"row:42", &mut seeded_rng(2))
+        .unwrap();
+
+    assert_eq!(aad::decrypt(&credentials, &message, b"row:42").unwrap(), b"record");
+    assert_kind!(aad::decrypt(&credentials, &message, b"row:43"), ErrorKind::HMACValidationFailed);
+    assert_kind!(aad::decrypt(&credentials, &message, b""), ErrorKind::HMACValidationFailed);
+}
+
+#[test]
+fn messages_are_not_interchangeable_with_stock_v3() {
+    let credentials = Credentials::Password("secret");
+    let message = aad::encrypt(&credentials, b"record", b"").unwrap();
+    assert_kind!(v3::decrypt("secret", &message), ErrorKind::HMACValidationFailed);
+    assert_eq!(aad::decrypt(&credentials, &message, b"").unwrap(), b"record");
+
+    let stock = v3::encrypt("secret", b"record").unwrap();
+    assert_kind!(aad::decrypt(&credentials, &stock, b""), ErrorKind::HMACValidationFailed);
+}
+
+#[test]
+fn the_length_of_the_associated_data_is_authenticated() {
+    // Moving bytes between the end of the cipher text and the associated data must not work,
+    // which is what the trailing length guards against.
+    let keys = KeyPair::generate_with_rng(&mut seeded_rng(3)).unwrap();
+    let credentials = keys.credentials();
+    let message = aad::encrypt_with_rng(&credentials, b"record", b"abc", &mut seeded_rng(4))
+        .unwrap();
+    assert_eq!(message.len(), 18 + 16 + 32);
+    assert_kind!(aad::decrypt(&credentials, &message, b"ab"), ErrorKind::HMACValidationFailed);
+    assert_kind!(aad::decrypt(&credentials, &message, b"abcd"), ErrorKind::HMACValidationFailed);
+}
@@ -0,0 +1,170 @@
+extern crate rncryptor;
+extern crate tar;
+
+#[macro_use]
+mod common;
+
+use std::env;
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::PathBuf;
+use std::process;
+
+use rncryptor::v3::archive::{decrypt_dir, encrypt_dir_with_rng};
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::stream::EncryptWriter;
+use rncryptor::v3::encryptor::Encryptor;
+use rncryptor::v3::types::*;
+use rncryptor::v3::rng::seeded_rng;
+
+fn keys() -> Credentials<'static> {
+    Credentials::Keys(EncryptionKey::from(vec![1; 32]), HMACKey::from(vec![2; 32]))
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("rncryptor-archive-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn directory_roundtrip() {
+    let src = scratch_dir("roundtrip-src");
+    fs::create_dir_all(src.join("nested/deeper")).unwrap();
+    fs::write(src.join("top.txt"), b"top level").unwrap();
+    fs::write(src.join("nested/deeper/data.bin"), vec![7u8; 100_000]).unwrap();
+    fs::create_dir_all(src.join("empty")).unwrap();
+
+    let password = Credentials::Password("thepassword");
+    let message = encrypt_dir_with_rng(&password, &src, Vec::new(), &mut seeded_rng(1)).unwrap();
+
+    let dest = scratch_dir("roundtrip-dest");
+    decrypt_dir(&password, Cursor::new(&message), &dest).unwrap();
+    assert_eq!(fs::read(dest.join("top.txt")).unwrap(), b"top level");
+    assert_eq!(fs::read(dest.join("nested/deeper/data.bin")).unwrap(), vec![7u8; 100_000]);
+    assert!(dest.join("empty").is_dir());
+    // Nothing else, such as the temporary extraction directory, is left behind.
+    assert_eq!(fs::read_dir(&dest).unwrap().count(), 3);
+
+    let _ = fs::remove_dir_all(&src);
+    let _ = fs::remove_dir_all(&dest);
+}
+
+#[test]
+fn tampered_archives_extract_nothing() {
+    let src = scratch_dir("tampered-src");
+    fs::write(src.join("file.txt"), b"content").unwrap();
+    let mut message = encrypt_dir_with_rng(&keys(), &src, Vec::new(), &mut seeded_rng(2)).unwrap();
+    message[100] ^= 1;
+
+    let dest = scratch_dir("tampered-dest");
+    assert!(decrypt_dir(&keys(), Cursor::new(&message), &dest).is_err());
+    assert_eq!(fs::read_dir(&dest).unwrap().count(), 0);
+
+    let _ = fs::remove_dir_all(&src);
+    let _ = fs::remove_dir_all(&dest);
+}
+
+#[test]
+fn conflicting_types_extract_nothing() {
+    let src = scratch_dir("conflict-src");
+    fs::write(src.join("a.txt"), b"a").unwrap();
+    fs::create_dir_all(src.join("nested")).unwrap();
+    fs::write(src.join("nested/file.txt"), b"nested").unwrap();
+    fs::write(src.join("z.txt"), b"z").unwrap();
+    let message = encrypt_dir_with_rng(&keys(), &src, Vec::new(), &mut seeded_rng(6)).unwrap();
+
+    // A file where the archive has a directory.
+    let dest = scratch_dir("conflict-file");
+    fs::write(dest.join("nested"), b"in the way").unwrap();
+    assert_kind!(decrypt_dir(&keys(), Cursor::new(&message), &dest),
+                 ErrorKind::Io(ref e) if e.kind() == io::ErrorKind::AlreadyExists);
+    assert_eq!(fs::read_dir(&dest).unwrap().count(), 1);
+    assert_eq!(fs::read(dest.join("nested")).unwrap(), b"in the way");
+    let _ = fs::remove_dir_all(&dest);
+
+    // A non-empty directory where the archive has a file.
+    let dest = scratch_dir("conflict-dir");
+    fs::create_dir_all(dest.join("z.txt")).unwrap();
+    fs::write(dest.join("z.txt/keep"), b"keep").unwrap();
+    assert_kind!(decrypt_dir(&keys(), Cursor::new(&message), &dest),
+                 ErrorKind::Io(ref e) if e.kind() == io::ErrorKind::AlreadyExists);
+    assert_eq!(fs::read_dir(&dest).unwrap().count(), 1);
+    assert_eq!(fs::read(dest.join("z.txt/keep")).unwrap(), b"keep");
+    let _ = fs::remove_dir_all(&dest);
+
+    let _ = fs::remove_dir_all(&src);
+}
+
+/// Encrypts a tar archive holding a single file at `path`, bypassing the path validation of
+/// `tar::Builder`.
+fn malicious_archive(path: &str) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut header = [0u8; 512];
+    header[..path.len()].copy_from_slice(path.as_bytes());
+    header[100..107].copy_from_slice(b"0000644");
+    header[124..135].copy_from_slice(b"00000000004");
+    header[136..147].copy_from_slice(b"00000000000");
+    header[156] = b'0';
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+    archive.extend_from_slice(&header);
+    archive.extend_from_slice(b"evil");
+    archive.resize(512 * 4, 0);
+
+    let encryptor = Encryptor::from_credentials_with_rng(&keys(), &mut seeded_rng(3)).unwrap();
+    let mut writer = EncryptWriter::new(&encryptor, Vec::new()).unwrap();
+    std::io::Write::write_all(&mut writer, &archive).unwrap();
+    writer.finish().unwrap()
+}
+
+#[test]
+fn path_traversal_is_rejected() {
+    for path in &["../evil.txt", "nested/../../evil.txt", "/tmp/rncryptor-evil.txt"] {
+        let dest = scratch_dir("traversal");
+        let message = malicious_archive(path);
+        assert_kind!(decrypt_dir(&keys(), Cursor::new(&message), dest.join("inner")),
+                     ErrorKind::InvalidFormat, "{}", path);
+        assert!(!dest.join("evil.txt").exists());
+        let _ = fs::remove_dir_all(&dest);
+    }
+    assert!(!PathBuf::from("/tmp/rncryptor-evil.txt").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn symbolic_links_are_not_archived() {
+    let src = scratch_dir("symlink-src");
+    fs::write(src.join("file.txt"), b"content").unwrap();
+    std::os::unix::fs::symlink("/etc/hostname", src.join("link")).unwrap();
+    assert_kind!(encrypt_dir_with_rng(&keys(), &src, Vec::new(), &mut seeded_rng(4)),
+                 ErrorKind::InvalidFormat);
+    let _ = fs::remove_dir_all(&src);
+}
+
+#[test]
+fn rejected_entries_extract_nothing() {
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(7);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, "file.txt", &b"content"[..]).unwrap();
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Symlink);
+    header.set_size(0);
+    builder.append_link(&mut header, "link", "/etc/hostname").unwrap();
+    let archive = builder.into_inner().unwrap();
+
+    let encryptor = Encryptor::from_credentials_with_rng(&keys(), &mut seeded_rng(5)).unwrap();
+    let mut writer = EncryptWriter::new(&encryptor, Vec::new()).unwrap();
+    std::io::Write::write_all(&mut writer, &archive).unwrap();
+    let message = writer.finish().unwrap();
+
+    let dest = scratch_dir("rejected-dest");
+    assert!(decrypt_dir(&keys(), Cursor::new(&message), &dest).is_err());
+    assert_eq!(fs::read_dir(&dest).unwrap().count(), 0);
+    let _ = fs::remove_dir_all(&dest);
+}
@@ -0,0 +1,57 @@
+extern crate rncryptor;
+extern crate rustc_serialize;
+
+#[macro_use]
+mod common;
+
+use rncryptor::v3::types::*;
+use rncryptor::v3::encryptor::Encryptor;
+use rncryptor::v3::decryptor::Decryptor;
+use rncryptor::v3::errors::ErrorKind;
+use rustc_serialize::hex::FromHex;
+
+fn encryptor() -> Encryptor {
+    let encryption_salt = Salt("0203040506070001".from_hex().unwrap());
+    let hmac_salt = Salt("0304050607080102".from_hex().unwrap());
+    let iv = IV::from("0405060708090a0b0c0d0e0f00010203".from_hex().unwrap());
+    Encryptor::from_password("thepassword", encryption_salt, hmac_salt, iv).unwrap()
+}
+
+#[test]
+fn encrypt_into_matches_encrypt() {
+    let e = encryptor();
+    let plain_text = b"0123456789abcdef01234567";
+    let mut buffer = [0u8; 128];
+    let len = e.encrypt_into(plain_text, &mut buffer).unwrap();
+    assert_eq!(len, e.message_len(plain_text.len()));
+    assert_eq!(buffer[..len].to_vec(), e.encrypt(plain_text).unwrap());
+}
+
+#[test]
+fn encrypt_into_rejects_short_buffers() {
+    let e = encryptor();
+    let mut buffer = [0u8; 32];
+    assert_kind!(e.encrypt_into(b"secret", &mut buffer),
+                 ErrorKind::BufferTooSmall(n) if n == e.message_len(6));
+}
+
+#[test]
+fn decrypt_into_roundtrip() {
+    let message = encryptor().encrypt(b"secret").unwrap();
+    let decryptor = Decryptor::from("thepassword", &message).unwrap();
+
+    let mut small = [0u8; 8];
+    assert!(decryptor.decrypt_into(&message, &mut small).is_err());
+
+    let mut buffer = [0u8; 16];
+    let len = decryptor.decrypt_into(&message, &mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"secret");
+}
+
+#[test]
+fn decrypt_rejects_tampered_messages() {
+    let mut message = encryptor().encrypt(b"secret").unwrap();
+    message[40] ^= 1;
+    let decryptor = Decryptor::from("thepassword", &message).unwrap();
+    assert_kind!(decryptor.decrypt(&message), ErrorKind::HMACValidationFailed);
+}
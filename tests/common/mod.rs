@@ -0,0 +1,63 @@
+//! Helpers shared by the integration tests, each of which only uses some of them.
+#![allow(dead_code, unused_macros)]
+
+use std::num::NonZeroU32;
+
+use rncryptor::v3::rng::{RngCore, CryptoRng, RngError};
+
+pub mod vectors;
+
+/// Asserts that `$result` failed with an error of the `$kind` pattern, optionally guarded, and
+/// panics otherwise, naming `$kind` and the optional context that follows it.
+macro_rules! assert_kind {
+    ($result:expr, $kind:pat $(if $guard:expr)?) => {
+        match $result {
+            Err(e) => {
+                match e.kind {
+                    $kind $(if $guard)? => {}
+                    other => panic!("expected {}, got {:?}", stringify!($kind), other),
+                }
+            }
+            Ok(_) => panic!("expected {}", stringify!($kind)),
+        }
+    };
+    ($result:expr, $kind:pat $(if $guard:expr)?, $($context:tt)+) => {
+        match $result {
+            Err(e) => {
+                match e.kind {
+                    $kind $(if $guard)? => {}
+                    other => {
+                        panic!("expected {} for {}, got {:?}",
+                               stringify!($kind),
+                               format_args!($($context)+),
+                               other)
+                    }
+                }
+            }
+            Ok(_) => panic!("expected {} for {}", stringify!($kind), format_args!($($context)+)),
+        }
+    };
+}
+
+/// A random number generator which always fails.
+pub struct FailingRng;
+
+impl RngCore for FailingRng {
+    fn next_u32(&mut self) -> u32 {
+        panic!("FailingRng can't generate anything")
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        panic!("FailingRng can't generate anything")
+    }
+
+    fn fill_bytes(&mut self, _: &mut [u8]) {
+        panic!("FailingRng can't generate anything")
+    }
+
+    fn try_fill_bytes(&mut self, _: &mut [u8]) -> Result<(), RngError> {
+        Err(RngError::from(NonZeroU32::new(RngError::CUSTOM_START).unwrap()))
+    }
+}
+
+impl CryptoRng for FailingRng {}
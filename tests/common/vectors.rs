@@ -0,0 +1,291 @@
+//! A parser for the test vector files published by the
+//! [RNCryptor-Spec](https://github.com/RNCryptor/RNCryptor-Spec/tree/master/vectors) repository.
+//!
+//! Each file is a sequence of records separated by blank lines, where every record is a list of
+//! `field: value` lines and lines starting with `#` are comments. Hex fields (ending in `_hex`)
+//! may contain whitespace between groups of digits.
+//!
+//! Only the `v3` vector files (and our own `hkdf` file) are given typed records, older versions
+//! can still be read as raw `TestVector`s.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::result::Result as StdResult;
+
+/// An error raised while parsing a test vector file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The (1-based) line where the error was detected.
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+pub type Result<T> = StdResult<T, ParseError>;
+
+/// A single record of a test vector file, as a map from field names to raw values.
+#[derive(Clone, Debug)]
+pub struct TestVector {
+    pub title: String,
+    pub version: u8,
+    /// The line where the record starts, useful to report failures.
+    pub line: usize,
+    fields: BTreeMap<String, String>,
+}
+
+impl TestVector {
+    /// Returns the raw value of `field`, if present.
+    pub fn field(&self, field: &str) -> Option<&str> {
+        self.fields.get(field).map(|s| s.as_str())
+    }
+
+    /// Returns the value of `field` decoded from hex, ignoring any whitespace.
+    pub fn hex_field(&self, field: &str) -> Result<Vec<u8>> {
+        match self.field(field) {
+            None => Err(ParseError {
+                line: self.line,
+                message: format!("missing field `{}` in \"{}\"", field, self.title),
+            }),
+            Some(v) => from_hex(v).map_err(|m| {
+                ParseError {
+                    line: self.line,
+                    message: format!("field `{}` in \"{}\": {}", field, self.title, m),
+                }
+            }),
+        }
+    }
+
+    fn required(&self, field: &str) -> Result<String> {
+        match self.field(field) {
+            Some(v) => Ok(v.to_owned()),
+            None => Err(ParseError {
+                line: self.line,
+                message: format!("missing field `{}` in \"{}\"", field, self.title),
+            }),
+        }
+    }
+}
+
+/// A vector from the `kdf` file.
+#[derive(Clone, Debug)]
+pub struct KdfVector {
+    pub title: String,
+    pub version: u8,
+    pub password: String,
+    pub salt: Vec<u8>,
+    pub key: Vec<u8>,
+}
+
+impl KdfVector {
+    pub fn from_vector(v: &TestVector) -> Result<KdfVector> {
+        Ok(KdfVector {
+            title: v.title.clone(),
+            version: v.version,
+            password: v.required("password")?,
+            salt: v.hex_field("salt_hex")?,
+            key: v.hex_field("key_hex")?,
+        })
+    }
+}
+
+/// A vector from the `key` file.
+#[derive(Clone, Debug)]
+pub struct KeyVector {
+    pub title: String,
+    pub version: u8,
+    pub encryption_key: Vec<u8>,
+    pub hmac_key: Vec<u8>,
+    pub iv: Vec<u8>,
+    pub plain_text: Vec<u8>,
+    pub cipher_text: Vec<u8>,
+}
+
+impl KeyVector {
+    pub fn from_vector(v: &TestVector) -> Result<KeyVector> {
+        Ok(KeyVector {
+            title: v.title.clone(),
+            version: v.version,
+            encryption_key: v.hex_field("enc_key_hex")?,
+            hmac_key: v.hex_field("hmac_key_hex")?,
+            iv: v.hex_field("iv_hex")?,
+            plain_text: v.hex_field("plaintext_hex")?,
+            cipher_text: v.hex_field("ciphertext_hex")?,
+        })
+    }
+}
+
+/// A vector from the `password` file.
+#[derive(Clone, Debug)]
+pub struct PasswordVector {
+    pub title: String,
+    pub version: u8,
+    pub password: String,
+    pub encryption_salt: Vec<u8>,
+    pub hmac_salt: Vec<u8>,
+    pub iv: Vec<u8>,
+    pub plain_text: Vec<u8>,
+    pub cipher_text: Vec<u8>,
+}
+
+impl PasswordVector {
+    pub fn from_vector(v: &TestVector) -> Result<PasswordVector> {
+        Ok(PasswordVector {
+            title: v.title.clone(),
+            version: v.version,
+            password: v.required("password")?,
+            encryption_salt: v.hex_field("enc_salt_hex")?,
+            hmac_salt: v.hex_field("hmac_salt_hex")?,
+            iv: v.hex_field("iv_hex")?,
+            plain_text: v.hex_field("plaintext_hex")?,
+            cipher_text: v.hex_field("ciphertext_hex")?,
+        })
+    }
+}
+
+/// A vector from the `hkdf` file, which isn't part of the spec.
+#[derive(Clone, Debug)]
+pub struct HkdfVector {
+    pub title: String,
+    pub version: u8,
+    pub master_secret: Vec<u8>,
+    pub salt: Vec<u8>,
+    pub info: Vec<u8>,
+    pub encryption_key: Vec<u8>,
+    pub hmac_key: Vec<u8>,
+}
+
+impl HkdfVector {
+    pub fn from_vector(v: &TestVector) -> Result<HkdfVector> {
+        Ok(HkdfVector {
+            title: v.title.clone(),
+            version: v.version,
+            master_secret: v.hex_field("master_secret_hex")?,
+            salt: v.hex_field("salt_hex")?,
+            info: v.hex_field("info_hex")?,
+            encryption_key: v.hex_field("enc_key_hex")?,
+            hmac_key: v.hex_field("hmac_key_hex")?,
+        })
+    }
+}
+
+/// Parses the content of a test vector file into its records.
+pub fn parse(input: &str) -> Result<Vec<TestVector>> {
+    let mut vectors = Vec::new();
+    let mut current: Option<(usize, BTreeMap<String, String>)> = None;
+
+    for (idx, raw_line) in input.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = raw_line.trim_end();
+
+        if line.trim().is_empty() {
+            if let Some((start, fields)) = current.take() {
+                vectors.push(to_vector(start, fields)?);
+            }
+            continue;
+        }
+
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = match line.find(':') {
+            Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+            None => {
+                return Err(ParseError {
+                    line: line_no,
+                    message: format!("expected `field: value`, found \"{}\"", line),
+                })
+            }
+        };
+
+        let (_, ref mut fields) = *current.get_or_insert((line_no, BTreeMap::new()));
+        if fields.insert(key.to_owned(), value.to_owned()).is_some() {
+            return Err(ParseError {
+                line: line_no,
+                message: format!("duplicate field `{}`", key),
+            });
+        }
+    }
+
+    if let Some((start, fields)) = current.take() {
+        vectors.push(to_vector(start, fields)?);
+    }
+
+    Ok(vectors)
+}
+
+/// Parses a `kdf` file.
+pub fn parse_kdf(input: &str) -> Result<Vec<KdfVector>> {
+    parse(input)?.iter().map(KdfVector::from_vector).collect()
+}
+
+/// Parses a `key` file.
+pub fn parse_key(input: &str) -> Result<Vec<KeyVector>> {
+    parse(input)?.iter().map(KeyVector::from_vector).collect()
+}
+
+/// Parses a `password` file.
+pub fn parse_password(input: &str) -> Result<Vec<PasswordVector>> {
+    parse(input)?.iter().map(PasswordVector::from_vector).collect()
+}
+
+/// Parses an `hkdf` file.
+pub fn parse_hkdf(input: &str) -> Result<Vec<HkdfVector>> {
+    parse(input)?.iter().map(HkdfVector::from_vector).collect()
+}
+
+fn to_vector(line: usize, mut fields: BTreeMap<String, String>) -> Result<TestVector> {
+    let title = match fields.remove("title") {
+        Some(t) => t,
+        None => {
+            return Err(ParseError {
+                line,
+                message: "missing field `title`".to_owned(),
+            })
+        }
+    };
+
+    let version = match fields.remove("version").map(|v| v.parse::<u8>()) {
+        Some(Ok(v)) => v,
+        Some(Err(_)) => {
+            return Err(ParseError {
+                line,
+                message: format!("invalid `version` in \"{}\"", title),
+            })
+        }
+        None => {
+            return Err(ParseError {
+                line,
+                message: format!("missing field `version` in \"{}\"", title),
+            })
+        }
+    };
+
+    Ok(TestVector {
+        title,
+        version,
+        line,
+        fields,
+    })
+}
+
+fn from_hex(input: &str) -> StdResult<Vec<u8>, String> {
+    let digits = input.chars().filter(|c| !c.is_whitespace()).collect::<Vec<_>>();
+    if digits.len() % 2 != 0 {
+        return Err("odd number of hex digits".to_owned());
+    }
+
+    let mut result = Vec::with_capacity(digits.len() / 2);
+    for pair in digits.chunks(2) {
+        match (pair[0].to_digit(16), pair[1].to_digit(16)) {
+            (Some(hi), Some(lo)) => result.push((hi * 16 + lo) as u8),
+            _ => return Err(format!("invalid hex digits \"{}{}\"", pair[0], pair[1])),
+        }
+    }
+    Ok(result)
+}
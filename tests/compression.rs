@@ -0,0 +1,102 @@
+extern crate flate2;
+extern crate rncryptor;
+
+#[macro_use]
+mod common;
+
+use std::io::Write;
+
+use rncryptor::v3;
+use rncryptor::v3::compression::{self, Compression, DEFAULT_MAX_LEN};
+use rncryptor::v3::encryptor::Encryptor;
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::types::*;
+use rncryptor::v3::rng::seeded_rng;
+
+fn keys() -> Credentials<'static> {
+    Credentials::Keys(EncryptionKey::from(vec![1; 32]), HMACKey::from(vec![2; 32]))
+}
+
+fn json(records: usize) -> Vec<u8> {
+    (0..records)
+        .map(|i| format!("{{\"id\":{},\"name\":\"customer\",\"active\":true}}\n", i))
+        .collect::<String>()
+        .into_bytes()
+}
+
+fn seal(plain_text: &[u8], compression: Compression) -> Message {
+    compression::encrypt_with_rng(&keys(), compression, plain_text, &mut seeded_rng(1)).unwrap()
+}
+
+#[test]
+fn roundtrips() {
+    let data = json(1000);
+    for &c in &[Compression::None, Compression::Deflate, Compression::Zstd] {
+        let message = seal(&data, c);
+        assert_eq!(compression::decrypt(&keys(), &message, DEFAULT_MAX_LEN).unwrap(), data);
+    }
+
+    let message = compression::encrypt(&Credentials::Password("thepassword"),
+                                       Compression::Zstd,
+                                       b"")
+        .unwrap();
+    assert!(compression::decrypt(&Credentials::Password("thepassword"), &message, 0)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn compressible_payloads_shrink() {
+    let data = json(1000);
+    let plain = seal(&data, Compression::None).len();
+    assert!(seal(&data, Compression::Deflate).len() * 5 < plain);
+    assert!(seal(&data, Compression::Zstd).len() * 5 < plain);
+}
+
+#[test]
+fn incompressible_payloads_are_stored() {
+    let mut data = vec![0u8; 4096];
+    let mut state = 0x12345678u32;
+    for b in data.iter_mut() {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        *b = state as u8;
+    }
+    assert_eq!(seal(&data, Compression::Zstd).len(), seal(&data, Compression::None).len());
+    assert_eq!(compression::decrypt(&keys(), &seal(&data, Compression::Zstd), 4096).unwrap(),
+               data);
+}
+
+#[test]
+fn limit_is_enforced() {
+    let message = seal(&json(100), Compression::Deflate);
+    assert_kind!(compression::decrypt(&keys(), &message, 100), ErrorKind::LimitExceeded(100));
+}
+
+#[test]
+fn understated_lengths_are_rejected() {
+    // A bomb: 64 MiB of zeros, claiming to be 16 bytes long.
+    let mut marked = b"RNCZ\x01".to_vec();
+    marked.extend_from_slice(&16u64.to_be_bytes());
+    let mut encoder = flate2::write::DeflateEncoder::new(marked, flate2::Compression::best());
+    encoder.write_all(&vec![0; 64 * 1024 * 1024]).unwrap();
+    let marked = encoder.finish().unwrap();
+
+    let encryptor = Encryptor::from_credentials_with_rng(&keys(), &mut seeded_rng(2)).unwrap();
+    let message = encryptor.encrypt(&marked).unwrap();
+    assert_kind!(compression::decrypt(&keys(), &message, DEFAULT_MAX_LEN),
+                 ErrorKind::InvalidFormat);
+}
+
+#[test]
+fn unmarked_messages_are_rejected() {
+    let encryptor = Encryptor::from_credentials_with_rng(&keys(), &mut seeded_rng(3)).unwrap();
+    let message = encryptor.encrypt(b"just some plain text").unwrap();
+    assert!(compression::decrypt(&keys(), &message, DEFAULT_MAX_LEN).is_err());
+
+    // Compressed messages are still regular v3 messages.
+    let message = compression::encrypt(&Credentials::Password("pw"), Compression::Zstd, b"hi")
+        .unwrap();
+    assert_eq!(&v3::decrypt("pw", &message).unwrap()[..4], b"RNCZ");
+}
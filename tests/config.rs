@@ -0,0 +1,177 @@
+extern crate rncryptor;
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate toml_edit;
+
+#[macro_use]
+mod common;
+
+use std::env;
+
+use rncryptor::v3::config::{self, Secret};
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::keys::KeyPair;
+use rncryptor::v3::rng::seeded_rng;
+use rncryptor::v3::types::Credentials;
+
+const PASSWORD: Credentials<'static> = Credentials::Password("config-secret");
+
+const TOML: &str = r#"# Production settings
+[database]
+host = "db.internal" # primary
+password = "hunter2"  # rotated every quarter
+port = 5432
+
+[[replicas]]
+host = "replica-1"
+"#;
+
+const YAML: &str = "# Production settings
+database:
+  host: db.internal # primary
+  password: 'hunter2'  # rotated every quarter
+  port: 5432
+replicas:
+  - host: replica-1
+";
+
+const JSON: &str = r#"{
+  "database": {
+    "host": "db.internal",
+    "password": "hunter2",
+    "port": 5432
+  },
+  "replicas": [{"host": "replica-1"}]
+}"#;
+
+#[test]
+fn values_round_trip() {
+    let encrypted = config::encrypt_value_with_rng(&PASSWORD, "hunter2", &mut seeded_rng(1))
+        .unwrap();
+    assert!(config::is_encrypted(&encrypted));
+    assert_eq!(config::decrypt_value(&PASSWORD, &encrypted).unwrap(), "hunter2");
+
+    assert!(!config::is_encrypted("hunter2"));
+    assert_kind!(config::decrypt_value(&PASSWORD, "ENC[not base64!]"), ErrorKind::InvalidFormat);
+}
+
+#[test]
+fn toml_keys_are_encrypted_in_place() {
+    let encrypted = config::encrypt_toml_keys(&PASSWORD, TOML, &["database.password",
+                                                                 "database.port"])
+        .unwrap();
+    assert!(encrypted.contains("# Production settings"));
+    assert!(encrypted.contains("host = \"db.internal\" # primary"));
+    assert!(encrypted.contains("  # rotated every quarter"));
+    assert!(!encrypted.contains("hunter2"));
+    assert_eq!(encrypted.lines().count(), TOML.lines().count());
+
+    // Encrypting again leaves the values as they are.
+    assert_eq!(config::encrypt_toml_keys(&PASSWORD, &encrypted, &["database.password"]).unwrap(),
+               encrypted);
+
+    let mut document = encrypted.parse::<toml_edit::DocumentMut>().unwrap();
+    assert_eq!(config::decrypt_toml(&PASSWORD, &mut document).unwrap(), 2);
+    assert_eq!(document["database"]["password"].as_str(), Some("hunter2"));
+    assert_eq!(document["database"]["port"].as_str(), Some("5432"));
+    assert_eq!(document["replicas"][0]["host"].as_str(), Some("replica-1"));
+}
+
+#[test]
+fn yaml_keys_are_encrypted_in_place() {
+    let encrypted = config::encrypt_yaml_keys(&PASSWORD, YAML, &["database.password"]).unwrap();
+    assert!(encrypted.contains("# Production settings"));
+    assert!(encrypted.contains("  host: db.internal # primary"));
+    assert!(encrypted.contains("  password: ENC["));
+    assert!(encrypted.contains("]  # rotated every quarter"));
+    assert_eq!(encrypted.lines().count(), YAML.lines().count());
+    assert_eq!(config::encrypt_yaml_keys(&PASSWORD, &encrypted, &["database.password"]).unwrap(),
+               encrypted);
+
+    // Values inside sequences can't be selected.
+    assert!(config::encrypt_yaml_keys(&PASSWORD, YAML, &["replicas.host"]).is_err());
+
+    let mut value: serde_yaml::Value = serde_yaml::from_str(&encrypted).unwrap();
+    assert_eq!(config::decrypt_yaml(&PASSWORD, &mut value).unwrap(), 1);
+    assert_eq!(value["database"]["password"].as_str(), Some("hunter2"));
+    assert_eq!(value["database"]["port"].as_u64(), Some(5432));
+}
+
+#[test]
+fn json_keys_are_encrypted_in_place() {
+    let encrypted = config::encrypt_json_keys(&PASSWORD, JSON, &["database.password",
+                                                                 "database.port"])
+        .unwrap();
+    assert!(encrypted.contains("\n    \"host\": \"db.internal\",\n"));
+    assert!(!encrypted.contains("hunter2"));
+    assert_eq!(encrypted.lines().count(), JSON.lines().count());
+    assert_eq!(config::encrypt_json_keys(&PASSWORD, &encrypted, &["database.port"]).unwrap(),
+               encrypted);
+
+    let mut value: serde_json::Value = serde_json::from_str(&encrypted).unwrap();
+    assert_eq!(config::decrypt_json(&PASSWORD, &mut value).unwrap(), 2);
+    assert_eq!(value["database"]["password"], "hunter2");
+    assert_eq!(value["database"]["port"], "5432");
+}
+
+#[test]
+fn missing_keys_are_errors() {
+    assert!(config::encrypt_json_keys(&PASSWORD, JSON, &["database.user"]).is_err());
+    assert!(config::encrypt_toml_keys(&PASSWORD, TOML, &["database.user"]).is_err());
+    assert!(config::encrypt_yaml_keys(&PASSWORD, YAML, &["database.user"]).is_err());
+    // Tables themselves aren't scalars.
+    assert!(config::encrypt_toml_keys(&PASSWORD, TOML, &["database"]).is_err());
+}
+
+#[test]
+fn repeated_paths_are_encrypted_once() {
+    let paths = ["database.password", "database.password"];
+    let json = config::encrypt_json_keys(&PASSWORD, JSON, &paths).unwrap();
+    let toml = config::encrypt_toml_keys(&PASSWORD, TOML, &paths).unwrap();
+    let yaml = config::encrypt_yaml_keys(&PASSWORD, YAML, &paths).unwrap();
+    for encrypted in &[json, toml, yaml] {
+        assert_eq!(encrypted.matches("ENC[").count(), 1);
+        assert!(!encrypted.contains("hunter2"));
+    }
+}
+
+#[test]
+fn yaml_block_scalars_are_not_mappings() {
+    let yaml = "script: |\n  password: hunter2\n  echo done\nport: 5432\n";
+    assert_kind!(config::encrypt_yaml_keys(&PASSWORD, yaml, &["password"]),
+                 ErrorKind::InvalidFormat);
+    assert_kind!(config::encrypt_yaml_keys(&PASSWORD, yaml, &["script"]),
+                 ErrorKind::InvalidFormat);
+    let encrypted = config::encrypt_yaml_keys(&PASSWORD, yaml, &["port"]).unwrap();
+    assert!(encrypted.starts_with("script: |\n  password: hunter2\n  echo done\nport: ENC["));
+
+    // A real key after a block scalar mimicking it is the one encrypted.
+    let yaml = "notes: >-\n  password: example\n\n  more notes\npassword: hunter2\n";
+    let encrypted = config::encrypt_yaml_keys(&PASSWORD, yaml, &["password"]).unwrap();
+    assert!(encrypted.starts_with("notes: >-\n  password: example\n\n  more notes\n\
+                                   password: ENC["));
+}
+
+#[test]
+fn secrets_are_read_from_the_environment() {
+    // The only test touching these variables, as the environment is shared between threads.
+    env::remove_var(config::PASSWORD_VAR);
+    env::remove_var(config::KEYS_VAR);
+    assert!(Secret::from_env().is_err());
+
+    env::set_var(config::PASSWORD_VAR, "config-secret");
+    let secret = Secret::from_env().unwrap();
+    assert_eq!(format!("{:?}", secret), "Password(<redacted>)");
+    let encrypted = config::encrypt_value(&PASSWORD, "hunter2").unwrap();
+    assert_eq!(config::decrypt_value(&secret.credentials(), &encrypted).unwrap(), "hunter2");
+
+    let keys = KeyPair::generate_with_rng(&mut seeded_rng(2)).unwrap();
+    env::set_var(config::KEYS_VAR, keys.to_hex());
+    assert!(Secret::from_env().is_err());
+
+    env::remove_var(config::PASSWORD_VAR);
+    let secret = Secret::from_env().unwrap();
+    let encrypted = config::encrypt_value(&keys.credentials(), "hunter2").unwrap();
+    assert_eq!(config::decrypt_value(&secret.credentials(), &encrypted).unwrap(), "hunter2");
+    env::remove_var(config::KEYS_VAR);
+}
@@ -0,0 +1,171 @@
+extern crate rncryptor;
+
+#[macro_use]
+mod common;
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+use rncryptor::v3::types::*;
+use rncryptor::v3::container::{ContainerReader, ContainerWriter};
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::rng::seeded_rng;
+
+fn keys() -> Credentials<'static> {
+    Credentials::Keys(EncryptionKey::from(vec![1; 32]), HMACKey::from(vec![2; 32]))
+}
+
+fn plain_text(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+fn seal(credentials: &Credentials, chunk_size: u32, data: &[u8]) -> Vec<u8> {
+    let mut writer = ContainerWriter::with_chunk_size(credentials, chunk_size, Vec::new(),
+                                                      seeded_rng(1))
+        .unwrap();
+    for piece in data.chunks(33) {
+        writer.write_all(piece).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+fn read_all(credentials: &Credentials, container: &[u8]) -> Vec<u8> {
+    let mut reader = ContainerReader::open(credentials, Cursor::new(container)).unwrap();
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    out
+}
+
+#[test]
+fn roundtrip_with_keys() {
+    for len in &[0, 1, 99, 100, 101, 1000] {
+        let data = plain_text(*len);
+        let container = seal(&keys(), 100, &data);
+        assert_eq!(read_all(&keys(), &container), data);
+    }
+}
+
+#[test]
+fn roundtrip_with_password() {
+    let data = plain_text(5000);
+    let password = Credentials::Password("thepassword");
+    let container = seal(&password, 1024, &data);
+    assert_eq!(read_all(&password, &container), data);
+
+    assert_kind!(ContainerReader::open(&keys(), Cursor::new(&container)), ErrorKind::InvalidFormat);
+}
+
+#[test]
+fn random_access() {
+    let data = plain_text(10_000);
+    let container = seal(&keys(), 256, &data);
+    let mut reader = ContainerReader::open(&keys(), Cursor::new(&container)).unwrap();
+    assert_eq!(reader.len(), 10_000);
+
+    for &(start, len) in &[(0, 10), (250, 20), (9_990, 10), (4_000, 1_000), (511, 2)] {
+        reader.seek(SeekFrom::Start(start as u64)).unwrap();
+        let mut buf = vec![0; len];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data[start..start + len]);
+    }
+
+    reader.seek(SeekFrom::End(-3)).unwrap();
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(&rest[..], &data[9_997..]);
+    assert!(reader.seek(SeekFrom::Current(-20_000)).is_err());
+}
+
+#[test]
+fn only_touched_chunks_are_authenticated() {
+    let data = plain_text(1000);
+    let mut container = seal(&keys(), 100, &data);
+    // Corrupts the last byte of the cipher text of the third chunk.
+    let header_len = 26;
+    let chunk_len = 18 + ((25 + 100) / 16 + 1) * 16 + 32;
+    container[header_len + 3 * chunk_len - 33] ^= 1;
+
+    let mut reader = ContainerReader::open(&keys(), Cursor::new(&container)).unwrap();
+    let mut buf = vec![0; 200];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[..], &data[..200]);
+    assert!(reader.read_exact(&mut buf).is_err());
+
+    reader.seek(SeekFrom::Start(300)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[..], &data[300..500]);
+}
+
+#[test]
+fn reordered_chunks_are_rejected() {
+    let data = plain_text(300);
+    let mut container = seal(&keys(), 100, &data);
+    let header_len = 26;
+    let chunk_len = 18 + ((25 + 100) / 16 + 1) * 16 + 32;
+    let first = container[header_len..header_len + chunk_len].to_vec();
+    let second = container[header_len + chunk_len..header_len + 2 * chunk_len].to_vec();
+    container[header_len..header_len + chunk_len].copy_from_slice(&second);
+    container[header_len + chunk_len..header_len + 2 * chunk_len].copy_from_slice(&first);
+
+    let mut reader = ContainerReader::open(&keys(), Cursor::new(&container)).unwrap();
+    let mut buf = vec![0; 10];
+    assert!(reader.read_exact(&mut buf).is_err());
+}
+
+#[test]
+fn truncated_containers_are_rejected() {
+    let data = plain_text(300);
+    let container = seal(&keys(), 100, &data);
+    let header_len = 26;
+    let chunk_len = 18 + ((25 + 100) / 16 + 1) * 16 + 32;
+
+    // Drops the last chunk, keeping the index.
+    let mut dropped = container[..header_len + 2 * chunk_len].to_vec();
+    dropped.extend_from_slice(&container[header_len + 3 * chunk_len..]);
+    assert!(ContainerReader::open(&keys(), Cursor::new(&dropped)).is_err());
+
+    // Drops the index.
+    let truncated = &container[..header_len + 3 * chunk_len];
+    assert!(ContainerReader::open(&keys(), Cursor::new(truncated)).is_err());
+}
+
+/// Fails the given write call (counting from 1), and only that one.
+struct FlakyWriter {
+    data: Vec<u8>,
+    writes: usize,
+    fail_at: usize,
+}
+
+impl Write for FlakyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writes += 1;
+        if self.writes == self.fail_at {
+            return Err(io::Error::other("flaky"));
+        }
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn failed_chunk_writes_can_be_retried() {
+    let data = plain_text(250);
+    let inner = FlakyWriter {
+        data: Vec::new(),
+        writes: 0,
+        // The header is the first write, the first chunk the second one.
+        fail_at: 2,
+    };
+    let mut writer = ContainerWriter::with_chunk_size(&keys(), 100, inner, seeded_rng(1)).unwrap();
+    assert_eq!(writer.write(&data[..100]).unwrap(), 100);
+    assert!(writer.write(&data[100..]).is_err());
+
+    let mut written = 100;
+    while written < data.len() {
+        written += writer.write(&data[written..]).unwrap();
+    }
+    let container = writer.finish().unwrap().data;
+    assert_eq!(read_all(&keys(), &container), data);
+}
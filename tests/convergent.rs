@@ -0,0 +1,59 @@
+extern crate rncryptor;
+
+use rncryptor::v3;
+use rncryptor::v3::convergent;
+use rncryptor::v3::decryptor::Decryptor;
+use rncryptor::v3::keys::KeyPair;
+use rncryptor::v3::rng::seeded_rng;
+use rncryptor::v3::types::Credentials;
+
+const CONVERGENCE_KEY: &[u8; 32] = b"0123456789abcdef0123456789abcdef";
+
+#[test]
+fn identical_chunks_give_identical_messages() {
+    let keys = KeyPair::generate_with_rng(&mut seeded_rng(1)).unwrap();
+    let credentials = keys.credentials();
+    let first = convergent::encrypt(&credentials, CONVERGENCE_KEY, b"chunk").unwrap();
+    let second = convergent::encrypt(&credentials, CONVERGENCE_KEY, b"chunk").unwrap();
+    assert_eq!(first, second);
+
+    assert_ne!(convergent::encrypt(&credentials, CONVERGENCE_KEY, b"chunk!").unwrap(), first);
+    assert_ne!(convergent::encrypt(&credentials, b"another convergence key, 32 bytes", b"chunk")
+                   .unwrap(),
+               first);
+
+    let decryptor = Decryptor::from_keys(keys.encryption_key(), keys.hmac_key(), &first).unwrap();
+    assert_eq!(decryptor.decrypt(&first).unwrap(), b"chunk");
+}
+
+#[test]
+fn password_messages_are_deterministic_and_stock_v3() {
+    let credentials = Credentials::Password("secret");
+    let first = convergent::encrypt(&credentials, CONVERGENCE_KEY, b"chunk").unwrap();
+    let second = convergent::encrypt(&credentials, CONVERGENCE_KEY, b"chunk").unwrap();
+    assert_eq!(first, second);
+    assert_eq!(v3::decrypt("secret", &first).unwrap(), b"chunk");
+}
+
+#[test]
+fn keys_can_be_derived_from_the_plain_text() {
+    let (keys, message) = convergent::encrypt_with_derived_keys(CONVERGENCE_KEY, b"chunk")
+        .unwrap();
+    let (same_keys, same_message) = convergent::encrypt_with_derived_keys(CONVERGENCE_KEY,
+                                                                          b"chunk")
+        .unwrap();
+    assert_eq!(keys, same_keys);
+    assert_eq!(message, same_message);
+    assert_eq!(convergent::derive_keys(CONVERGENCE_KEY, b"chunk").unwrap(), keys);
+    assert_ne!(convergent::derive_keys(CONVERGENCE_KEY, b"other").unwrap(), keys);
+
+    let decryptor = Decryptor::from_keys(keys.encryption_key(), keys.hmac_key(), &message)
+        .unwrap();
+    assert_eq!(decryptor.decrypt(&message).unwrap(), b"chunk");
+}
+
+#[test]
+fn short_convergence_keys_are_rejected() {
+    assert!(convergent::encrypt(&Credentials::Password("secret"), b"short", b"chunk").is_err());
+    assert!(convergent::encrypt_with_derived_keys(b"short", b"chunk").is_err());
+}
@@ -0,0 +1,107 @@
+extern crate rncryptor;
+
+#[macro_use]
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use rncryptor::v3::envelope::{self, Envelope, KeyManager, LocalKeyManager};
+use rncryptor::v3::errors::{Error, ErrorKind, Result};
+use rncryptor::v3::rng::seeded_rng;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("rncryptor-envelope-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// A toy `KeyManager`, XOR-ing data keys with a single byte per key id.
+struct XorKeyManager;
+
+impl KeyManager for XorKeyManager {
+    fn wrap(&self, key_id: &str, data_key: &[u8]) -> Result<Vec<u8>> {
+        if key_id != "xor" {
+            return Err(Error::new(ErrorKind::InvalidFormat, "unknown key id".to_owned()));
+        }
+        Ok(data_key.iter().map(|b| b ^ 0x5a).collect())
+    }
+
+    fn unwrap(&self, key_id: &str, wrapped_key: &[u8]) -> Result<Vec<u8>> {
+        self.wrap(key_id, wrapped_key)
+    }
+}
+
+#[test]
+fn custom_key_managers() {
+    let sealed = envelope::seal_with_rng(&XorKeyManager, "xor", b"payload", &mut seeded_rng(1))
+        .unwrap();
+    assert_eq!(sealed.key_id, "xor");
+    assert_eq!(envelope::open(&XorKeyManager, &sealed).unwrap(), b"payload");
+    assert!(envelope::seal(&XorKeyManager, "other", b"payload").is_err());
+
+    let boxed: Box<dyn KeyManager> = Box::new(XorKeyManager);
+    assert_eq!(envelope::open(&*boxed, &sealed).unwrap(), b"payload");
+}
+
+#[test]
+fn local_key_manager_roundtrip() {
+    let dir = scratch_dir("local");
+    let manager = LocalKeyManager::open(&dir).unwrap();
+    manager.create_key("master-2024").unwrap();
+    manager.create_key("master-2025").unwrap();
+    assert!(manager.create_key("master-2024").is_err());
+
+    let first = envelope::seal(&manager, "master-2024", b"customer record").unwrap();
+    let second = envelope::seal(&manager, "master-2024", b"customer record").unwrap();
+    assert_ne!(first.wrapped_key, second.wrapped_key);
+    assert_ne!(first.message, second.message);
+
+    let bytes = first.to_bytes().unwrap();
+    assert_eq!(&bytes[..4], b"RNCE");
+    let parsed = Envelope::from_bytes(&bytes).unwrap();
+    assert_eq!(parsed, first);
+    assert_eq!(envelope::open(&manager, &parsed).unwrap(), b"customer record");
+
+    // Pointing at another master key makes unwrapping fail.
+    let mut wrong = parsed.clone();
+    wrong.key_id = "master-2025".to_owned();
+    assert!(envelope::open(&manager, &wrong).is_err());
+
+    // So does swapping the wrapped data keys.
+    let mut swapped = parsed.clone();
+    swapped.wrapped_key = second.wrapped_key.clone();
+    assert!(envelope::open(&manager, &swapped).is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn key_ids_are_validated() {
+    let dir = scratch_dir("ids");
+    let manager = LocalKeyManager::open(&dir).unwrap();
+    for id in &["", "../escape", "a/b", ".hidden"] {
+        assert_kind!(manager.create_key(id), ErrorKind::InvalidFormat, "{:?}", id);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn malformed_envelopes_are_rejected() {
+    let sealed = envelope::seal_with_rng(&XorKeyManager, "xor", b"payload", &mut seeded_rng(2))
+        .unwrap();
+    let bytes = sealed.to_bytes().unwrap();
+    assert!(Envelope::from_bytes(&bytes[..10]).is_err());
+    assert!(Envelope::from_bytes(b"RNCX\x01\x00").is_err());
+
+    let mut version = bytes.clone();
+    version[4] = 2;
+    assert!(Envelope::from_bytes(&version).is_err());
+
+    let mut tampered = bytes.clone();
+    let last = tampered.len() - 40;
+    tampered[last] ^= 1;
+    assert!(envelope::open(&XorKeyManager, &Envelope::from_bytes(&tampered).unwrap()).is_err());
+}
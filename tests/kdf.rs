@@ -0,0 +1,63 @@
+extern crate rncryptor;
+extern crate rustc_serialize;
+
+use rncryptor::v3::kdf;
+use rncryptor::v3::types::*;
+use rncryptor::v3::encryptor::Encryptor;
+use rustc_serialize::hex::FromHex;
+
+#[test]
+fn derive_key_uses_the_spec_iterations() {
+    let salt = Salt("0203040506070801".from_hex().unwrap());
+    assert_eq!(kdf::derive_key(b"thepassword", &salt).to_vec(),
+               kdf::derive_key_with_iterations(b"thepassword", &salt, 10_000).to_vec());
+}
+
+#[test]
+fn derive_key_with_custom_iterations() {
+    let salt = Salt("0203040506070801".from_hex().unwrap());
+    let expected = "3d3f64cd 31d8057b 5624567a 2caeace4 84adcfda 3fbf6401 a1f03a09 5cee6dd1"
+        .from_hex()
+        .unwrap();
+    assert_eq!(kdf::derive_key_with_iterations(b"thepassword", &salt, 1_000).to_vec(),
+               expected);
+}
+
+#[test]
+fn derived_keys_match_password_based_encryption() {
+    let encryption_salt = Salt("0102030405060700".from_hex().unwrap());
+    let hmac_salt = Salt("0203040506070801".from_hex().unwrap());
+    let iv = IV::from("030405060708090a0b0c0d0e0f000102".from_hex().unwrap());
+    let plain_text = "0123456789abcdef".from_hex().unwrap();
+
+    let (ek, hk) = kdf::derive_keys(b"thepassword", &encryption_salt, &hmac_salt);
+    let key_based = Encryptor::from_keys(ek, hk, iv.clone())
+        .and_then(|e| e.encrypt(&plain_text))
+        .unwrap();
+    let password_based = Encryptor::from_password("thepassword", encryption_salt, hmac_salt, iv)
+        .and_then(|e| e.encrypt(&plain_text))
+        .unwrap();
+
+    // Same cipher text, only the headers and thus the HMACs differ.
+    assert_eq!(key_based[18..key_based.len() - 32],
+               password_based[34..password_based.len() - 32]);
+}
+
+#[test]
+fn hkdf_separates_contexts() {
+    let master = [7u8; 32];
+    let (ek1, hk1) = kdf::hkdf_keys(&master, b"tenant:acme").unwrap();
+    let (ek2, hk2) = kdf::hkdf_keys(&master, b"tenant:globex").unwrap();
+    assert!(ek1.to_vec() != ek2.to_vec());
+    assert!(hk1 != hk2);
+    assert!(ek1.to_vec() != hk1.to_vec());
+
+    let (again, _) = kdf::hkdf_keys(&master, b"tenant:acme").unwrap();
+    assert_eq!(ek1.to_vec(), again.to_vec());
+}
+
+#[test]
+fn hkdf_rejects_short_master_secrets() {
+    assert!(kdf::hkdf_keys(&[0; kdf::MIN_MASTER_SECRET_LEN - 1], b"info").is_err());
+    assert!(kdf::hkdf_keys(&[0; kdf::MIN_MASTER_SECRET_LEN], b"info").is_ok());
+}
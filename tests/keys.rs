@@ -0,0 +1,87 @@
+extern crate rncryptor;
+
+#[macro_use]
+mod common;
+
+use rncryptor::v3::keys::{KeyPair, KEYFILE_PREFIX};
+use rncryptor::v3::encryptor::Encryptor;
+use rncryptor::v3::decryptor::Decryptor;
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::types::*;
+use rncryptor::v3::rng::seeded_rng;
+
+use common::FailingRng;
+
+fn pair() -> KeyPair {
+    KeyPair::from_bytes(&(0..64).collect::<Vec<u8>>()).unwrap()
+}
+
+#[test]
+fn generated_pairs_encrypt_and_decrypt() {
+    let pair = KeyPair::generate().unwrap();
+    assert_ne!(pair, KeyPair::generate().unwrap());
+    let message = Encryptor::from_credentials(&pair.credentials()).unwrap().encrypt(b"data").unwrap();
+    let decryptor = Decryptor::from_keys(pair.encryption_key(), pair.hmac_key(), &message).unwrap();
+    assert_eq!(decryptor.decrypt(&message).unwrap(), b"data");
+}
+
+#[test]
+fn seeded_generation_is_reproducible() {
+    assert_eq!(KeyPair::generate_with_rng(&mut seeded_rng(5)).unwrap(),
+               KeyPair::generate_with_rng(&mut seeded_rng(5)).unwrap());
+}
+
+#[test]
+fn generation_failures_are_reported() {
+    assert_kind!(KeyPair::generate_with_rng(&mut FailingRng), ErrorKind::KeyGenerationFailed(_));
+}
+
+#[test]
+fn hex_and_base64() {
+    let pair = pair();
+    let hex = format!("{}", pair);
+    assert_eq!(&hex[..8], "00010203");
+    assert_eq!(hex.len(), 128);
+    assert_eq!(hex.parse::<KeyPair>().unwrap(), pair);
+    assert_eq!(hex.to_uppercase().parse::<KeyPair>().unwrap(), pair);
+
+    let base64 = format!("{:#}", pair);
+    assert_eq!(base64, pair.to_base64());
+    assert_eq!(base64.len(), 88);
+    assert_eq!(base64.parse::<KeyPair>().unwrap(), pair);
+}
+
+#[test]
+fn keyfiles() {
+    let pair = pair();
+    let keyfile = pair.to_keyfile();
+    assert!(keyfile.starts_with(KEYFILE_PREFIX));
+    assert_eq!(KeyPair::from_keyfile(&format!("\n  {}  \n", keyfile)).unwrap(), pair);
+
+    for bad in &["rncryptor-keypair:v2:AAAA", "AAAA", ""] {
+        assert_kind!(KeyPair::from_keyfile(bad), ErrorKind::InvalidFormat, "{:?}", bad);
+    }
+}
+
+#[test]
+fn lengths_are_validated() {
+    assert_kind!(KeyPair::from_bytes(&[0; 63]), ErrorKind::WrongInputSize(63));
+    assert_kind!(KeyPair::new(EncryptionKey::from(vec![0; 16]), HMACKey::from(vec![0; 32])),
+                 ErrorKind::WrongInputSize(16));
+    assert_kind!(KeyPair::from_hex("0011"), ErrorKind::WrongInputSize(2));
+    assert_kind!(KeyPair::from_base64("AAAA"), ErrorKind::WrongInputSize(3));
+    assert_kind!(KeyPair::from_keyfile(&format!("{}AAAA", KEYFILE_PREFIX)),
+                 ErrorKind::WrongInputSize(3));
+
+    let not_hex = "+f".repeat(64);
+    assert!(KeyPair::from_hex(&not_hex).is_err());
+}
+
+#[test]
+fn debug_is_redacted() {
+    let pair = pair();
+    let printed = format!("{:?} {:?} {:?}", pair, pair.encryption_key(), pair.hmac_key());
+    assert!(!printed.contains("0, 1, 2"));
+    assert!(!printed.contains("000102"));
+    assert!(printed.contains("redacted"));
+}
@@ -0,0 +1,90 @@
+extern crate rncryptor;
+
+#[macro_use]
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use rncryptor::v3::keys::KeyPair;
+use rncryptor::v3::keystore::{self, Keystore};
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::rng::seeded_rng;
+use common::FailingRng;
+
+fn keys() -> KeyPair {
+    KeyPair::generate_with_rng(&mut seeded_rng(1)).unwrap()
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("rncryptor-keystore-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn create_and_unlock() {
+    let keystore = Keystore::create_with_rng("secret", "invoices", &keys(), 1_700_000_000,
+                                             &mut seeded_rng(2))
+        .unwrap();
+    assert_eq!(keystore.info().label, "invoices");
+    assert_eq!(keystore.info().created, 1_700_000_000);
+    assert_eq!(keystore.info().id.len(), 32);
+    assert_eq!(keystore.unlock("secret").unwrap(), keys());
+
+    assert_kind!(keystore.unlock("wrong"), ErrorKind::HMACValidationFailed);
+
+    assert_kind!(Keystore::create_with_rng("secret", "invoices", &keys(), 0, &mut FailingRng),
+                 ErrorKind::KeyGenerationFailed(_));
+}
+
+#[test]
+fn text_roundtrip_and_tampered_metadata() {
+    let keystore = Keystore::create("secret", "payroll", &keys()).unwrap();
+    let text = keystore.to_string();
+    assert!(text.starts_with("rncryptor-keystore:v1\n"));
+
+    let parsed: Keystore = text.parse().unwrap();
+    assert_eq!(parsed.info(), keystore.info());
+    assert_eq!(parsed.unlock("secret").unwrap(), keys());
+
+    let relabelled: Keystore = text.replace("label: payroll", "label: public").parse().unwrap();
+    assert_kind!(relabelled.unlock("secret"), ErrorKind::InvalidFormat);
+    assert_kind!(relabelled.change_password("secret", "new"), ErrorKind::InvalidFormat);
+
+    assert!("rncryptor-keystore:v2\n".parse::<Keystore>().is_err());
+    assert!(Keystore::create("secret", "two\nlines", &keys()).is_err());
+}
+
+#[test]
+fn change_password() {
+    let keystore = Keystore::create("old", "label", &keys()).unwrap();
+    assert!(keystore.change_password("wrong", "new").is_err());
+
+    let changed = keystore.change_password("old", "new").unwrap();
+    assert_eq!(changed.info(), keystore.info());
+    assert_eq!(changed.unlock("new").unwrap(), keys());
+    assert!(changed.unlock("old").is_err());
+}
+
+#[test]
+fn save_load_and_list() {
+    let dir = scratch_dir("list");
+    let first = Keystore::create("a", "first", &keys()).unwrap();
+    let second = Keystore::create("b", "second", &KeyPair::generate().unwrap()).unwrap();
+    first.save(dir.join("a.rnkeystore")).unwrap();
+    second.save(dir.join("b.rnkeystore")).unwrap();
+    fs::write(dir.join("notes.txt"), "not a keystore").unwrap();
+
+    let listed = keystore::list(&dir).unwrap();
+    assert_eq!(listed.len(), 2);
+    assert_eq!(&listed[0].1, first.info());
+    assert_eq!(&listed[1].1, second.info());
+
+    let loaded = Keystore::load(dir.join("a.rnkeystore")).unwrap();
+    assert_eq!(loaded.unlock("a").unwrap(), keys());
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,71 @@
+extern crate rncryptor;
+
+#[macro_use]
+mod common;
+
+use rncryptor::v3::multi_password::MultiPasswordMessage;
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::rng::seeded_rng;
+
+fn sealed() -> MultiPasswordMessage {
+    MultiPasswordMessage::seal_with_rng(&["alice", "bob"], b"team secrets", &mut seeded_rng(1))
+        .unwrap()
+}
+
+#[test]
+fn every_password_opens_the_payload() {
+    let message = sealed();
+    assert_eq!(message.slot_count(), 2);
+    assert_eq!(message.open("alice").unwrap(), b"team secrets");
+    assert_eq!(message.open("bob").unwrap(), b"team secrets");
+    assert_kind!(message.open("mallory"), ErrorKind::HMACValidationFailed);
+}
+
+#[test]
+fn slots_are_added_and_removed_without_reencrypting() {
+    let mut message = sealed();
+    // header (2) | IV (16) | one block of cipher text (16) | HMAC (32)
+    let payload_len = 66;
+    let payload = message.to_bytes();
+    let payload = payload[payload.len() - payload_len..].to_vec();
+
+    assert!(message.add_slot("mallory", "carol").is_err());
+    message.add_slot("bob", "carol").unwrap();
+    assert_eq!(message.slot_of("carol").unwrap(), 2);
+    assert_eq!(message.open("carol").unwrap(), b"team secrets");
+
+    let index = message.slot_of("alice").unwrap();
+    message.remove_slot(index).unwrap();
+    assert!(message.open("alice").is_err());
+    assert_eq!(message.open("bob").unwrap(), b"team secrets");
+
+    let bytes = message.to_bytes();
+    assert_eq!(bytes[bytes.len() - payload_len..].to_vec(), payload);
+}
+
+#[test]
+fn the_last_slot_stays() {
+    let mut message = MultiPasswordMessage::seal(&["only"], b"data").unwrap();
+    assert!(message.remove_slot(1).is_err());
+    assert!(message.remove_slot(0).is_err());
+    assert_eq!(message.open("only").unwrap(), b"data");
+    assert!(MultiPasswordMessage::seal(&[], b"data").is_err());
+}
+
+#[test]
+fn bytes_roundtrip() {
+    let message = sealed();
+    let bytes = message.to_bytes();
+    assert_eq!(&bytes[..4], b"RNCM");
+    let parsed = MultiPasswordMessage::from_bytes(&bytes).unwrap();
+    assert_eq!(parsed, message);
+    assert_eq!(parsed.open("bob").unwrap(), b"team secrets");
+
+    assert!(MultiPasswordMessage::from_bytes(&bytes[..50]).is_err());
+    assert!(MultiPasswordMessage::from_bytes(b"RNCM\x01\x00").is_err());
+
+    let mut tampered = bytes.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert!(MultiPasswordMessage::from_bytes(&tampered).unwrap().open("alice").is_err());
+}
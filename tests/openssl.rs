@@ -0,0 +1,68 @@
+extern crate rncryptor;
+extern crate rustc_serialize;
+
+#[macro_use]
+mod common;
+
+use rustc_serialize::base64::FromBase64;
+
+use rncryptor::v3;
+use rncryptor::v3::openssl;
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::rng::seeded_rng;
+use rncryptor::v3::types::Credentials;
+
+// printf 'hello from openssl\n' | openssl enc -aes-256-cbc -pbkdf2 -salt -pass pass:ops-secret
+const DEFAULT_ITERATIONS: &str = "U2FsdGVkX1939QxMwW1GBAd7BB6WI7GXPsiOBYhqgVzMzGTPbMGp+sKxplzzqddu";
+// Same, with -iter 1000.
+const THOUSAND_ITERATIONS: &str = "U2FsdGVkX1/E8D3cgO6N73qsyadMf9TA7K6NMh4dhZa/Aq/ggvAmXa/mZ2bUuoKb";
+
+#[test]
+fn openssl_files_are_decrypted() {
+    let data = DEFAULT_ITERATIONS.from_base64().unwrap();
+    assert_eq!(openssl::decrypt("ops-secret", &data, openssl::DEFAULT_ITERATIONS).unwrap(),
+               b"hello from openssl\n");
+
+    let data = THOUSAND_ITERATIONS.from_base64().unwrap();
+    assert_eq!(openssl::decrypt("ops-secret", &data, 1000).unwrap(),
+               b"hello from openssl\n");
+    assert!(openssl::decrypt("ops-secret", &data, 0).is_err());
+}
+
+#[test]
+fn wrong_passwords_and_formats_are_rejected() {
+    let data = DEFAULT_ITERATIONS.from_base64().unwrap();
+    assert_kind!(openssl::decrypt("wrong", &data, openssl::DEFAULT_ITERATIONS),
+                 ErrorKind::DecryptionFailed(_));
+    assert_kind!(openssl::decrypt("ops-secret", b"Unsalted", 1), ErrorKind::InvalidFormat);
+    assert!(openssl::decrypt("ops-secret", &data[..data.len() - 1], 1).is_err());
+}
+
+#[test]
+fn openssl_files_convert_to_v3_and_back() {
+    let data = DEFAULT_ITERATIONS.from_base64().unwrap();
+    let credentials = Credentials::Password("v3-secret");
+    let message = openssl::to_v3_with_rng("ops-secret",
+                                          &data,
+                                          openssl::DEFAULT_ITERATIONS,
+                                          &credentials,
+                                          &mut seeded_rng(1))
+        .unwrap();
+    assert_eq!(v3::decrypt("v3-secret", &message).unwrap(), b"hello from openssl\n");
+
+    let exported = openssl::from_v3_with_rng(&credentials,
+                                             &message,
+                                             "partner",
+                                             1000,
+                                             &mut seeded_rng(2))
+        .unwrap();
+    assert_eq!(&exported[..8], b"Salted__");
+    assert_eq!(exported.len(), 16 + 32);
+    assert_eq!(openssl::decrypt("partner", &exported, 1000).unwrap(),
+               b"hello from openssl\n");
+
+    let mut tampered = message.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert!(openssl::from_v3(&credentials, &tampered, "partner", 1000).is_err());
+}
@@ -0,0 +1,66 @@
+extern crate rncryptor;
+
+#[macro_use]
+mod common;
+
+use rncryptor::v3;
+use rncryptor::v3::padding::{self, Padding};
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::keys::KeyPair;
+use rncryptor::v3::rng::seeded_rng;
+use rncryptor::v3::types::Credentials;
+
+fn credentials() -> Credentials<'static> {
+    KeyPair::generate_with_rng(&mut seeded_rng(1)).unwrap().credentials()
+}
+
+fn message_len(padding: Padding, plain_text_len: usize) -> usize {
+    padding::encrypt_with_rng(&credentials(),
+                              padding,
+                              &vec![b'x'; plain_text_len],
+                              &mut seeded_rng(2))
+        .unwrap()
+        .len()
+}
+
+#[test]
+fn padded_messages_roundtrip() {
+    let credentials = credentials();
+    for padding in &[Padding::Padme, Padding::PowerOfTwo, Padding::Bucket(100)] {
+        for len in &[0, 1, 15, 16, 17, 500, 5000] {
+            let plain_text = vec![7; *len];
+            let message = padding::encrypt(&credentials, *padding, &plain_text).unwrap();
+            assert_eq!(padding::decrypt(&credentials, &message).unwrap(), plain_text);
+        }
+    }
+
+    let password = Credentials::Password("secret");
+    let message = padding::encrypt(&password, Padding::Padme, b"template").unwrap();
+    assert_eq!(padding::decrypt(&password, &message).unwrap(), b"template");
+}
+
+#[test]
+fn lengths_within_a_bucket_are_indistinguishable() {
+    // The header (18 bytes) and the HMAC (32 bytes) surround the padded cipher text.
+    assert_eq!(message_len(Padding::Bucket(256), 10), 18 + 256 + 32);
+    assert_eq!(message_len(Padding::Bucket(256), 200), 18 + 256 + 32);
+    assert_eq!(message_len(Padding::Bucket(256), 300), 18 + 512 + 32);
+
+    assert_eq!(message_len(Padding::PowerOfTwo, 100), 18 + 128 + 32);
+    assert_eq!(message_len(Padding::PowerOfTwo, 115), 18 + 128 + 32);
+    assert_eq!(message_len(Padding::PowerOfTwo, 116), 18 + 256 + 32);
+
+    assert_eq!(message_len(Padding::Padme, 1000), message_len(Padding::Padme, 1010));
+    assert_eq!(padding::padded_len(Padding::Padme, 1000).unwrap(), 1024);
+    assert!(padding::padded_len(Padding::Padme, 100_000).unwrap() < 112_000);
+}
+
+#[test]
+fn invalid_paddings_are_rejected() {
+    assert!(padding::encrypt(&credentials(), Padding::Bucket(0), b"data").is_err());
+
+    // A stock v3 message has no padding marker.
+    let message = v3::encrypt("secret", b"data").unwrap();
+    assert_kind!(padding::decrypt(&Credentials::Password("secret"), &message),
+                 ErrorKind::InvalidFormat);
+}
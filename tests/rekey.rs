@@ -0,0 +1,232 @@
+extern crate rncryptor;
+extern crate rustc_serialize;
+
+#[macro_use]
+mod common;
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+use rncryptor::v3;
+use rncryptor::v3::types::*;
+use rncryptor::v3::encryptor::Encryptor;
+use rncryptor::v3::decryptor::Decryptor;
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::rekey::{rekey, rekey_message};
+use rncryptor::v3::stream::{self, EncryptWriter};
+use rustc_serialize::hex::FromHex;
+
+fn keys(seed: u8) -> Credentials<'static> {
+    Credentials::Keys(EncryptionKey::from(vec![seed; 32]), HMACKey::from(vec![seed + 1; 32]))
+}
+
+/// A `Message` whose first chunk is damaged once it has been read through, that is right after
+/// the HMAC validation pass.
+struct ChangingReader {
+    inner: Cursor<Vec<u8>>,
+    changed: bool,
+}
+
+impl ChangingReader {
+    fn new(message: Message) -> ChangingReader {
+        ChangingReader {
+            inner: Cursor::new(message),
+            changed: false,
+        }
+    }
+}
+
+impl Read for ChangingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if !self.changed && self.inner.position() == self.inner.get_ref().len() as u64 {
+            self.inner.get_mut()[100] ^= 1;
+            self.changed = true;
+        }
+        Ok(len)
+    }
+}
+
+impl Seek for ChangingReader {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+/// A writer failing once, when more than `limit` bytes have been written to it.
+struct FailingWriter {
+    written: Vec<u8>,
+    limit: Option<usize>,
+}
+
+impl Write for FailingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(limit) = self.limit {
+            if self.written.len() == limit {
+                self.limit = None;
+                return Err(io::Error::other("disk full"));
+            }
+            let len = buf.len().min(limit - self.written.len());
+            self.written.extend_from_slice(&buf[..len]);
+            return Ok(len);
+        }
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn decrypt(credentials: &Credentials, message: &Message) -> Vec<u8> {
+    Decryptor::from_credentials(credentials, message).unwrap().decrypt(message).unwrap()
+}
+
+#[test]
+fn encrypt_writer_matches_encrypt() {
+    let iv = IV::from("0405060708090a0b0c0d0e0f00010203".from_hex().unwrap());
+    let e = Encryptor::from_keys(EncryptionKey::from(vec![1; 32]), HMACKey::from(vec![2; 32]), iv)
+        .unwrap();
+    for len in &[0, 1, 15, 16, 17, 100_000] {
+        let plain_text = vec![7u8; *len];
+        let mut writer = EncryptWriter::new(&e, Vec::new()).unwrap();
+        for chunk in plain_text.chunks(13) {
+            writer.write_all(chunk).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), e.encrypt(&plain_text).unwrap());
+    }
+}
+
+#[test]
+fn encrypt_writer_keeps_failing_after_a_write_error() {
+    let e = Encryptor::from_credentials(&keys(1)).unwrap();
+    let inner = FailingWriter {
+        written: Vec::new(),
+        limit: Some(100),
+    };
+    let mut writer = EncryptWriter::new(&e, inner).unwrap();
+    writer.write_all(&[7; 64]).unwrap();
+    assert_eq!(writer.write_all(&[7; 64]).unwrap_err().kind(), io::ErrorKind::Other);
+
+    // The underlying writer would accept a retry, which mustn't encrypt anything twice.
+    assert_eq!(writer.write(&[7; 64]).unwrap_err().kind(), io::ErrorKind::Other);
+    assert_eq!(writer.get_ref().written.len(), 100);
+    assert_kind!(writer.finish(), ErrorKind::Io(_));
+}
+
+#[test]
+fn stream_roundtrip() {
+    let plain_text = vec![42u8; 200_000];
+    let credentials = Credentials::Password("thepassword");
+    let message = stream::encrypt(&credentials, &plain_text[..], Vec::new()).unwrap();
+    assert_eq!(v3::decrypt("thepassword", &message).unwrap(), plain_text);
+
+    let mut out = Vec::new();
+    let len = stream::decrypt(&credentials, Cursor::new(&message), &mut out).unwrap();
+    assert_eq!(len, plain_text.len() as u64);
+    assert_eq!(out, plain_text);
+}
+
+#[test]
+fn password_to_password() {
+    let message = v3::encrypt("old", b"the plain text").unwrap();
+    let rekeyed = rekey_message(&message,
+                                &Credentials::Password("old"),
+                                &Credentials::Password("new"))
+        .unwrap();
+    assert_eq!(v3::decrypt("new", &rekeyed).unwrap(), b"the plain text");
+    assert!(v3::decrypt("old", &rekeyed).is_err());
+}
+
+#[test]
+fn password_to_keys_and_back() {
+    let message = v3::encrypt("old", b"the plain text").unwrap();
+    let rekeyed = rekey_message(&message, &Credentials::Password("old"), &keys(1)).unwrap();
+    assert_eq!(decrypt(&keys(1), &rekeyed), b"the plain text");
+
+    let back = rekey_message(&rekeyed, &keys(1), &Credentials::Password("new")).unwrap();
+    assert_eq!(v3::decrypt("new", &back).unwrap(), b"the plain text");
+}
+
+#[test]
+fn keys_to_keys_from_an_offset() {
+    let message = Encryptor::from_credentials(&keys(1)).unwrap().encrypt(b"offset").unwrap();
+    let mut input = b"prefix".to_vec();
+    input.extend(message);
+    let mut cursor = Cursor::new(input);
+    cursor.set_position(6);
+
+    let rekeyed = rekey(cursor, &keys(1), &keys(3), Vec::new()).unwrap();
+    assert_eq!(decrypt(&keys(3), &rekeyed), b"offset");
+}
+
+#[test]
+fn wrong_old_password_writes_nothing() {
+    let message = v3::encrypt("old", b"the plain text").unwrap();
+    let mut out = Vec::new();
+    assert_kind!(rekey(Cursor::new(&message),
+                       &Credentials::Password("wrong"),
+                       &Credentials::Password("new"),
+                       &mut out),
+                 ErrorKind::HMACValidationFailed);
+    assert!(out.is_empty());
+}
+
+#[test]
+fn tampered_message_writes_nothing() {
+    let mut message = v3::encrypt("old", &vec![1u8; 1000]).unwrap();
+    message[500] ^= 1;
+    let mut out = Vec::new();
+    assert!(rekey(Cursor::new(&message), &Credentials::Password("old"), &keys(1), &mut out)
+        .is_err());
+    assert!(out.is_empty());
+}
+
+#[test]
+fn truncated_message_is_rejected() {
+    let message = v3::encrypt("old", b"the plain text").unwrap();
+    let mut out = Vec::new();
+    assert!(stream::decrypt(&Credentials::Password("old"),
+                            Cursor::new(&message[..message.len() - 1]),
+                            &mut out)
+        .is_err());
+    assert!(out.is_empty());
+}
+
+#[test]
+fn plain_text_reader() {
+    let plain_text = vec![9u8; 150_000];
+    let message = v3::encrypt("thepassword", &plain_text).unwrap();
+    let reader = stream::MessageReader::new(&Credentials::Password("thepassword"),
+                                            Cursor::new(&message))
+        .unwrap();
+    let mut out = Vec::new();
+    reader.into_plain_text().unwrap().read_to_end(&mut out).unwrap();
+    assert_eq!(out, plain_text);
+}
+
+#[test]
+fn changes_between_passes_hold_back_the_end_of_the_plain_text() {
+    let plain_text = vec![5u8; 200_000];
+    let message = v3::encrypt("thepassword", &plain_text).unwrap();
+    let mut out = Vec::new();
+    assert_kind!(stream::decrypt(&Credentials::Password("thepassword"),
+                                 ChangingReader::new(message),
+                                 &mut out),
+                 ErrorKind::HMACValidationFailed);
+    assert!(out.len() < plain_text.len());
+}
+
+#[test]
+fn plain_text_reader_keeps_failing_after_an_error() {
+    let message = v3::encrypt("thepassword", &vec![5u8; 200_000]).unwrap();
+    let reader = stream::MessageReader::new(&Credentials::Password("thepassword"),
+                                            ChangingReader::new(message))
+        .unwrap();
+    let mut plain_text = reader.into_plain_text().unwrap();
+    let mut out = Vec::new();
+    assert_eq!(plain_text.read_to_end(&mut out).unwrap_err().kind(),
+               io::ErrorKind::InvalidData);
+    assert!(out.len() < 200_000);
+    assert_eq!(plain_text.read(&mut [0; 16]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,54 @@
+extern crate rncryptor;
+
+use rncryptor::v3;
+use rncryptor::v3::types::*;
+use rncryptor::v3::encryptor::Encryptor;
+use rncryptor::v3::rng::seeded_rng;
+
+#[test]
+fn seeded_salts_and_ivs_are_reproducible() {
+    let (mut a, mut b) = (seeded_rng(42), seeded_rng(42));
+    assert_eq!(Salt::new_with_rng(&mut a).unwrap().as_slice(),
+               Salt::new_with_rng(&mut b).unwrap().as_slice());
+    assert_eq!(IV::new_with_rng(&mut a).unwrap(),
+               IV::new_with_rng(&mut b).unwrap());
+    assert_eq!(IV::new_with_rng(&mut a).unwrap().as_slice().len(), 16);
+}
+
+#[test]
+fn encrypt_with_rng_is_deterministic() {
+    let first = v3::encrypt_with_rng("password", b"secret", &mut seeded_rng(7)).unwrap();
+    let second = v3::encrypt_with_rng("password", b"secret", &mut seeded_rng(7)).unwrap();
+    let other = v3::encrypt_with_rng("password", b"secret", &mut seeded_rng(8)).unwrap();
+    assert_eq!(first, second);
+    assert!(first != other);
+    assert_eq!(v3::decrypt("password", &first).unwrap(), b"secret".to_vec());
+}
+
+#[test]
+fn encryptor_with_rng_matches_explicit_parameters() {
+    let mut rng = seeded_rng(1);
+    let es = Salt::new_with_rng(&mut rng).unwrap();
+    let hs = Salt::new_with_rng(&mut rng).unwrap();
+    let iv = IV::new_with_rng(&mut rng).unwrap();
+    let expected = Encryptor::from_password("password", es, hs, iv)
+        .and_then(|e| e.encrypt(b"secret"))
+        .unwrap();
+    let actual = Encryptor::from_password_with_rng("password", &mut seeded_rng(1))
+        .and_then(|e| e.encrypt(b"secret"))
+        .unwrap();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn key_based_encryptor_with_rng() {
+    let ek = EncryptionKey::from(vec![1; 32]);
+    let hk = HMACKey::from(vec![2; 32]);
+    let a = Encryptor::from_keys_with_rng(ek.clone(), hk.clone(), &mut seeded_rng(3))
+        .and_then(|e| e.encrypt(b"secret"))
+        .unwrap();
+    let b = Encryptor::from_keys(ek, hk, IV::new_with_rng(&mut seeded_rng(3)).unwrap())
+        .and_then(|e| e.encrypt(b"secret"))
+        .unwrap();
+    assert_eq!(a, b);
+}
@@ -0,0 +1,86 @@
+extern crate rncryptor;
+
+use rncryptor::v3;
+use rncryptor::v3::salvage::unauthenticated_salvage;
+use rncryptor::v3::keys::KeyPair;
+use rncryptor::v3::rng::seeded_rng;
+use rncryptor::v3::types::Credentials;
+
+fn plain_text() -> Vec<u8> {
+    (0..100u8).collect()
+}
+
+fn encrypted() -> (Credentials<'static>, Vec<u8>) {
+    let keys = KeyPair::generate_with_rng(&mut seeded_rng(1)).unwrap();
+    let credentials = keys.credentials();
+    let message = v3::encryptor::Encryptor::from_credentials_with_rng(&credentials,
+                                                                      &mut seeded_rng(2))
+        .unwrap()
+        .encrypt(&plain_text())
+        .unwrap();
+    (credentials, message)
+}
+
+#[test]
+fn intact_messages_are_fully_recovered() {
+    let (credentials, message) = encrypted();
+    let salvaged = unauthenticated_salvage(&credentials, &message).unwrap();
+    assert!(salvaged.hmac_valid);
+    assert!(!salvaged.truncated);
+    assert!(salvaged.suspect_blocks.is_empty());
+    assert_eq!(salvaged.data, plain_text());
+}
+
+#[test]
+fn a_damaged_hmac_loses_nothing() {
+    let (credentials, mut message) = encrypted();
+    let last = message.len() - 1;
+    message[last] ^= 0xff;
+    assert!(v3::decryptor::Decryptor::from_credentials(&credentials, &message)
+        .unwrap()
+        .decrypt(&message)
+        .is_err());
+
+    let salvaged = unauthenticated_salvage(&credentials, &message).unwrap();
+    assert!(!salvaged.hmac_valid);
+    assert_eq!(salvaged.data, plain_text());
+}
+
+#[test]
+fn a_damaged_last_block_is_reported() {
+    let (credentials, mut message) = encrypted();
+    // 100 bytes are 7 blocks: damage the last one, just before the HMAC.
+    let position = message.len() - 32 - 1;
+    message[position] ^= 0xff;
+
+    let salvaged = unauthenticated_salvage(&credentials, &message).unwrap();
+    assert!(!salvaged.hmac_valid);
+    assert_eq!(salvaged.suspect_blocks, vec![5, 6]);
+    assert_eq!(salvaged.data.len(), 7 * 16);
+    assert_eq!(&salvaged.data[..6 * 16], &plain_text()[..6 * 16]);
+}
+
+#[test]
+fn truncated_messages_keep_their_whole_blocks() {
+    let (credentials, message) = encrypted();
+    // Lose the HMAC and half of the last block.
+    let truncated = &message[..message.len() - 32 - 8];
+
+    let salvaged = unauthenticated_salvage(&credentials, truncated).unwrap();
+    assert!(salvaged.truncated);
+    assert!(!salvaged.hmac_valid);
+    assert_eq!(salvaged.data, &plain_text()[..6 * 16]);
+    assert_eq!(salvaged.suspect_blocks, vec![5]);
+
+    assert!(unauthenticated_salvage(&credentials, &message[..20]).is_err());
+}
+
+#[test]
+fn password_messages_can_be_salvaged() {
+    let message = v3::encrypt_with_rng("secret", &plain_text(), &mut seeded_rng(3)).unwrap();
+    let salvaged = unauthenticated_salvage(&Credentials::Password("secret"),
+                                           &message[..message.len() - 4])
+        .unwrap();
+    assert!(salvaged.truncated);
+    assert_eq!(&salvaged.data[..6 * 16], &plain_text()[..6 * 16]);
+}
@@ -0,0 +1,88 @@
+extern crate rncryptor;
+
+#[macro_use]
+mod common;
+
+use rncryptor::v3;
+use rncryptor::v3::signed::{self, SignedMessage, SigningKey, VerifyingKey};
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::rng::seeded_rng;
+use rncryptor::v3::types::Credentials;
+use common::FailingRng;
+
+#[test]
+fn trusted_signatures_are_verified_before_decrypting() {
+    let mut rng = seeded_rng(1);
+    let sender = SigningKey::generate_with_rng(&mut rng).unwrap();
+    let other = SigningKey::generate_with_rng(&mut rng).unwrap();
+    let message = v3::encrypt_with_rng("secret", b"signed payload", &mut rng).unwrap();
+    let signed = signed::sign(&sender, &message);
+    let password = Credentials::Password("secret");
+
+    let trusted = [other.verifying_key(), sender.verifying_key()];
+    assert_eq!(signed::verify(&trusted, &signed).unwrap(), &message);
+    assert_eq!(signed::verify_and_decrypt(&trusted, &password, &signed).unwrap(),
+               b"signed payload");
+
+    assert_kind!(signed::verify_and_decrypt(&[other.verifying_key()], &password, &signed),
+                 ErrorKind::SignatureValidationFailed);
+    assert_kind!(signed::verify(&[], &signed), ErrorKind::SignatureValidationFailed);
+}
+
+#[test]
+fn tampering_breaks_the_signature() {
+    let mut rng = seeded_rng(2);
+    let sender = SigningKey::generate_with_rng(&mut rng).unwrap();
+    let message = v3::encrypt_with_rng("secret", b"data", &mut rng).unwrap();
+    let signed = signed::sign(&sender, &message);
+    let trusted = [sender.verifying_key()];
+
+    let mut tampered = signed.clone();
+    let last = tampered.message.len() - 1;
+    tampered.message[last] ^= 1;
+    assert_kind!(signed::verify(&trusted, &tampered), ErrorKind::SignatureValidationFailed);
+
+    let mut tampered = signed.clone();
+    tampered.signature[0] ^= 1;
+    assert_kind!(signed::verify(&trusted, &tampered), ErrorKind::SignatureValidationFailed);
+
+    // Re-signing someone else's message under a trusted key needs that key.
+    let impostor = SigningKey::generate_with_rng(&mut rng).unwrap();
+    let mut forged = signed::sign(&impostor, &message);
+    forged.signer = sender.verifying_key();
+    assert_kind!(signed::verify(&trusted, &forged), ErrorKind::SignatureValidationFailed);
+}
+
+#[test]
+fn signed_messages_roundtrip_through_bytes() {
+    let mut rng = seeded_rng(3);
+    let sender = SigningKey::generate_with_rng(&mut rng).unwrap();
+    let message = v3::encrypt_with_rng("secret", b"data", &mut rng).unwrap();
+    let signed = signed::sign(&sender, &message);
+
+    let bytes = signed.to_bytes();
+    assert_eq!(&bytes[..4], b"RNCS");
+    assert_eq!(bytes.len(), 5 + 32 + 64 + message.len());
+    assert_eq!(SignedMessage::from_bytes(&bytes).unwrap(), signed);
+    assert!(SignedMessage::from_bytes(&bytes[..50]).is_err());
+    assert!(SignedMessage::from_bytes(b"RNCS\x02").is_err());
+}
+
+#[test]
+fn keys_roundtrip_through_strings() {
+    let key = SigningKey::generate().unwrap();
+    let serialized = key.to_string();
+    assert!(serialized.starts_with("rncryptor-ed25519-signing:v1:"));
+    assert_eq!(serialized.parse::<SigningKey>().unwrap().to_bytes(), key.to_bytes());
+    assert_eq!(format!("{:?}", key), "SigningKey(<redacted>)");
+
+    let public = key.verifying_key();
+    assert_eq!(public.to_string().parse::<VerifyingKey>().unwrap(), public);
+    assert_eq!(VerifyingKey::from_bytes(&public.to_bytes()).unwrap(), public);
+    assert!(serialized.parse::<VerifyingKey>().is_err());
+}
+
+#[test]
+fn generation_failures_are_reported() {
+    assert_kind!(SigningKey::generate_with_rng(&mut FailingRng), ErrorKind::KeyGenerationFailed(_));
+}
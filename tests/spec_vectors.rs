@@ -0,0 +1,99 @@
+extern crate rncryptor;
+
+mod common;
+
+use rncryptor::v3;
+use rncryptor::v3::kdf;
+use rncryptor::v3::types::*;
+use rncryptor::v3::encryptor::Encryptor;
+use rncryptor::v3::decryptor::Decryptor;
+use common::vectors;
+
+const KDF: &str = include_str!("../vectors/v3/kdf");
+const KEY: &str = include_str!("../vectors/v3/key");
+const PASSWORD: &str = include_str!("../vectors/v3/password");
+const HKDF: &str = include_str!("../vectors/v3/hkdf");
+
+#[test]
+fn parses_records_and_comments() {
+    let input = "# a comment\n\ntitle: First\nversion: 3\nplaintext_hex:\nkey_hex: 0102 0304\n\n\
+                 title: Second\nversion: 2\npassword: a: b\n";
+    let parsed = vectors::parse(input).unwrap();
+    assert_eq!(parsed.len(), 2);
+    assert_eq!(parsed[0].title, "First");
+    assert_eq!(parsed[0].line, 3);
+    assert_eq!(parsed[0].hex_field("plaintext_hex").unwrap(), Vec::<u8>::new());
+    assert_eq!(parsed[0].hex_field("key_hex").unwrap(), vec![1, 2, 3, 4]);
+    assert_eq!(parsed[1].version, 2);
+    assert_eq!(parsed[1].field("password"), Some("a: b"));
+}
+
+#[test]
+fn rejects_malformed_records() {
+    assert_eq!(vectors::parse("title: x\nversion: 3\nnot a field\n").unwrap_err().line, 3);
+    assert!(vectors::parse("version: 3\n").is_err());
+    assert!(vectors::parse("title: x\n").is_err());
+    assert!(vectors::parse("title: x\nversion: 3\nkey_hex: 0g\n").unwrap()[0]
+        .hex_field("key_hex")
+        .is_err());
+}
+
+#[test]
+fn kdf_vectors() {
+    let vectors = vectors::parse_kdf(KDF).unwrap();
+    assert!(!vectors.is_empty());
+    for v in vectors.into_iter().filter(|v| v.version == 3) {
+        let actual = kdf::derive_key(v.password.as_bytes(), &Salt(v.salt.clone()));
+        assert!(actual.to_vec() == v.key, "kdf: {}", v.title);
+    }
+}
+
+#[test]
+fn key_vectors() {
+    let vectors = vectors::parse_key(KEY).unwrap();
+    assert!(!vectors.is_empty());
+    for v in vectors.into_iter().filter(|v| v.version == 3) {
+        let encrypted = Encryptor::from_keys(EncryptionKey::from(v.encryption_key.clone()),
+                                             HMACKey::from(v.hmac_key.clone()),
+                                             IV::from(v.iv.clone()))
+            .and_then(|e| e.encrypt(&v.plain_text))
+            .unwrap();
+        assert!(encrypted == v.cipher_text, "key (encrypt): {}", v.title);
+
+        let decrypted = Decryptor::from_keys(EncryptionKey::from(v.encryption_key.clone()),
+                                             HMACKey::from(v.hmac_key.clone()),
+                                             &v.cipher_text)
+            .and_then(|d| d.decrypt(&v.cipher_text))
+            .unwrap();
+        assert!(decrypted == v.plain_text, "key (decrypt): {}", v.title);
+    }
+}
+
+#[test]
+fn password_vectors() {
+    let vectors = vectors::parse_password(PASSWORD).unwrap();
+    assert!(!vectors.is_empty());
+    for v in vectors.into_iter().filter(|v| v.version == 3) {
+        let encrypted = Encryptor::from_password(&v.password,
+                                                 Salt(v.encryption_salt.clone()),
+                                                 Salt(v.hmac_salt.clone()),
+                                                 IV::from(v.iv.clone()))
+            .and_then(|e| e.encrypt(&v.plain_text))
+            .unwrap();
+        assert!(encrypted == v.cipher_text, "password (encrypt): {}", v.title);
+
+        let decrypted = v3::decrypt(&v.password, &v.cipher_text).unwrap();
+        assert!(decrypted == v.plain_text, "password (decrypt): {}", v.title);
+    }
+}
+
+#[test]
+fn hkdf_vectors() {
+    let vectors = vectors::parse_hkdf(HKDF).unwrap();
+    assert!(!vectors.is_empty());
+    for v in vectors {
+        let (ek, hk) = kdf::hkdf_keys_with_salt(&v.master_secret, &v.salt, &v.info).unwrap();
+        assert!(ek.to_vec() == &v.encryption_key, "hkdf encryption key: {}", v.title);
+        assert!(hk.to_vec() == &v.hmac_key, "hkdf hmac key: {}", v.title);
+    }
+}
@@ -0,0 +1,68 @@
+extern crate rncryptor;
+
+#[macro_use]
+mod common;
+
+use std::num::NonZeroU64;
+
+use rncryptor::v3::token;
+use rncryptor::v3::errors::{Error, ErrorKind};
+use rncryptor::v3::keys::KeyPair;
+use rncryptor::v3::rng::seeded_rng;
+
+const ISSUED_AT: u64 = 1_700_000_000;
+
+fn keys() -> KeyPair {
+    KeyPair::generate_with_rng(&mut seeded_rng(1)).unwrap()
+}
+
+fn issue(payload: &[u8], ttl: Option<u64>) -> String {
+    let ttl = ttl.map(|ttl| NonZeroU64::new(ttl).unwrap());
+    token::encode_with_rng(&keys(), payload, ttl, &|| ISSUED_AT, &mut seeded_rng(2)).unwrap()
+}
+
+fn decode_at(token: &str, now: u64) -> Result<Vec<u8>, Error> {
+    token::decode_with_clock(&keys(), token, &move || now)
+}
+
+#[test]
+fn tokens_are_valid_until_they_expire() {
+    let token = issue(b"reset:alice", Some(600));
+    assert!(token.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+
+    assert_eq!(decode_at(&token, ISSUED_AT).unwrap(), b"reset:alice");
+    assert_eq!(decode_at(&token, ISSUED_AT + 599).unwrap(), b"reset:alice");
+    assert_kind!(decode_at(&token, ISSUED_AT + 600), ErrorKind::TokenExpired);
+}
+
+#[test]
+fn future_tokens_are_rejected_beyond_the_clock_skew() {
+    let token = issue(b"link", Some(600));
+    let now = ISSUED_AT - token::MAX_CLOCK_SKEW;
+    assert_eq!(decode_at(&token, now).unwrap(), b"link");
+    assert_kind!(decode_at(&token, now - 1), ErrorKind::TokenNotYetValid);
+}
+
+#[test]
+fn tokens_without_ttl_never_expire() {
+    let token = issue(b"forever", None);
+    assert_eq!(decode_at(&token, u64::MAX).unwrap(), b"forever");
+
+    let token = token::encode(&keys(), b"now", NonZeroU64::new(60)).unwrap();
+    assert_eq!(token::decode(&keys(), &token).unwrap(), b"now");
+}
+
+#[test]
+fn forged_or_foreign_tokens_are_rejected() {
+    let token = issue(b"reset:alice", Some(600));
+    let other_keys = KeyPair::generate_with_rng(&mut seeded_rng(3)).unwrap();
+    assert_kind!(token::decode_with_clock(&other_keys, &token, &|| ISSUED_AT),
+                 ErrorKind::HMACValidationFailed);
+
+    let mut tampered = token.into_bytes();
+    let last = tampered.len() - 2;
+    tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+    assert_kind!(decode_at(&String::from_utf8(tampered).unwrap(), ISSUED_AT),
+                 ErrorKind::HMACValidationFailed);
+    assert_kind!(decode_at("not a token!", ISSUED_AT), ErrorKind::InvalidFormat);
+}
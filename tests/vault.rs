@@ -0,0 +1,115 @@
+extern crate rncryptor;
+
+#[macro_use]
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+
+use rncryptor::v3::vault::Vault;
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::rng::seeded_rng;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("rncryptor-vault-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn vault() -> Vault {
+    let mut rng = seeded_rng(1);
+    let mut vault = Vault::create_with_rng("vault-secret", &mut rng).unwrap();
+    assert!(!vault.add_with_rng("github", b"ghp_token", &mut rng).unwrap());
+    assert!(!vault.add_with_rng("aws", b"AKIA...", &mut rng).unwrap());
+    assert!(!vault.add_with_rng("empty", b"", &mut rng).unwrap());
+    vault
+}
+
+#[test]
+fn add_get_remove_and_list() {
+    let mut vault = vault();
+    assert_eq!(vault.list(), vec!["aws", "empty", "github"]);
+    assert_eq!(vault.get("github").unwrap().unwrap(), b"ghp_token");
+    assert_eq!(vault.get("empty").unwrap().unwrap(), b"");
+    assert_eq!(vault.get("gitlab").unwrap(), None);
+
+    assert!(vault.add_with_rng("github", b"ghp_rotated", &mut seeded_rng(2)).unwrap());
+    assert_eq!(vault.get("github").unwrap().unwrap(), b"ghp_rotated");
+    assert!(vault.remove("aws"));
+    assert!(!vault.remove("aws"));
+    assert_eq!(vault.len(), 2);
+    assert!(vault.add_with_rng("", b"nameless", &mut seeded_rng(3)).is_err());
+    assert!(!format!("{:?}", vault).contains("ghp_"));
+}
+
+#[test]
+fn vaults_round_trip_and_change_password() {
+    let mut vault = vault();
+    let bytes = vault.to_bytes_with_rng(&mut seeded_rng(2)).unwrap();
+    let opened = Vault::from_bytes(&bytes, "vault-secret").unwrap();
+    assert_eq!(opened.list(), vault.list());
+    assert_eq!(opened.get("aws").unwrap().unwrap(), b"AKIA...");
+    assert_kind!(Vault::from_bytes(&bytes, "wrong"), ErrorKind::HMACValidationFailed);
+
+    vault.change_password_with_rng("new-secret", &mut seeded_rng(3)).unwrap();
+    let bytes = vault.to_bytes_with_rng(&mut seeded_rng(4)).unwrap();
+    assert_kind!(Vault::from_bytes(&bytes, "vault-secret"), ErrorKind::HMACValidationFailed);
+    let opened = Vault::from_bytes(&bytes, "new-secret").unwrap();
+    assert_eq!(opened.get("github").unwrap().unwrap(), b"ghp_token");
+}
+
+#[test]
+fn tampered_vaults_are_rejected() {
+    let bytes = vault().to_bytes_with_rng(&mut seeded_rng(2)).unwrap();
+
+    // The last byte is part of the HMAC of the last secret, which the index records.
+    let mut tampered = bytes.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert_kind!(Vault::from_bytes(&tampered, "vault-secret"), ErrorKind::HMACValidationFailed);
+
+    let mut tampered = bytes.clone();
+    tampered.push(0);
+    assert!(Vault::from_bytes(&tampered, "vault-secret").is_err());
+    assert!(Vault::from_bytes(&bytes[..bytes.len() - 1], "vault-secret").is_err());
+    assert!(Vault::from_bytes(b"RNCX\x01", "vault-secret").is_err());
+}
+
+#[test]
+fn saved_vaults_can_be_read_while_being_written() {
+    let path = scratch_dir("concurrent").join("secrets.rnvault");
+    vault().save(&path).unwrap();
+
+    let writer_path = path.clone();
+    let writer = thread::spawn(move || {
+        let mut vault = Vault::open(&writer_path, "vault-secret").unwrap();
+        for i in 0..5 {
+            vault.add(&format!("secret-{}", i), b"value").unwrap();
+            vault.save(&writer_path).unwrap();
+        }
+    });
+    for _ in 0..5 {
+        let vault = Vault::open(&path, "vault-secret").unwrap();
+        assert_eq!(vault.get("github").unwrap().unwrap(), b"ghp_token");
+    }
+    writer.join().unwrap();
+
+    let vault = Vault::open(&path, "vault-secret").unwrap();
+    assert_eq!(vault.len(), 8);
+    assert!(!path.with_extension("rnvault.tmp").exists());
+}
+
+#[test]
+fn failed_saves_leave_no_temporary_file() {
+    let dir = scratch_dir("failed-save");
+    // Renaming a file over a directory fails.
+    let path = dir.join("secrets.rnvault");
+    fs::create_dir(&path).unwrap();
+    assert!(vault().save(&path).is_err());
+    assert!(path.is_dir());
+    assert!(!path.with_extension("rnvault.tmp").exists());
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,78 @@
+extern crate rncryptor;
+
+#[macro_use]
+mod common;
+
+use rncryptor::v3;
+use rncryptor::v3::x25519::{self, Identity, Recipient};
+use rncryptor::v3::errors::ErrorKind;
+use rncryptor::v3::rng::seeded_rng;
+use common::FailingRng;
+
+#[test]
+fn every_recipient_can_decrypt() {
+    let mut rng = seeded_rng(1);
+    let backend = Identity::generate_with_rng(&mut rng).unwrap();
+    let backup = Identity::generate_with_rng(&mut rng).unwrap();
+    let stranger = Identity::generate_with_rng(&mut rng).unwrap();
+
+    let message = x25519::encrypt_with_rng(&[backend.recipient(), backup.recipient()],
+                                           b"upload",
+                                           &mut rng)
+        .unwrap();
+    assert_eq!(&message[..4], b"RNCX");
+    assert_eq!(x25519::decrypt(&backend, &message).unwrap(), b"upload");
+    assert_eq!(x25519::decrypt(&backup, &message).unwrap(), b"upload");
+    assert_kind!(x25519::decrypt(&stranger, &message), ErrorKind::HMACValidationFailed);
+}
+
+#[test]
+fn the_payload_is_a_regular_v3_message() {
+    let identity = Identity::generate().unwrap();
+    let message = x25519::encrypt(&[identity.recipient()], b"payload").unwrap();
+    // magic, version, count | ephemeral key | wrapped keys
+    let payload = &message[6 + 32 + 130..];
+    assert_eq!(payload[0], 3);
+    assert_eq!(payload[1], 0);
+    assert!(v3::decrypt("any password", &payload.to_vec()).is_err());
+}
+
+#[test]
+fn identities_and_recipients_roundtrip_through_strings() {
+    let identity = Identity::generate_with_rng(&mut seeded_rng(2)).unwrap();
+    let serialized = identity.to_string();
+    assert!(serialized.starts_with("rncryptor-x25519-identity:v1:"));
+    let parsed: Identity = serialized.parse().unwrap();
+    assert_eq!(parsed.to_bytes(), identity.to_bytes());
+    assert_eq!(format!("{:?}", identity), "Identity(<redacted>)");
+
+    let recipient = identity.recipient();
+    let parsed: Recipient = recipient.to_string().parse().unwrap();
+    assert_eq!(parsed, recipient);
+    assert_eq!(Recipient::from_bytes(recipient.to_bytes()), recipient);
+
+    assert!(serialized.replace("identity", "recipient").parse::<Identity>().is_err());
+    assert!("rncryptor-x25519-recipient:v1:AAAA".parse::<Recipient>().is_err());
+}
+
+#[test]
+fn tampering_and_degenerate_keys_are_rejected() {
+    let mut rng = seeded_rng(3);
+    let identity = Identity::generate_with_rng(&mut rng).unwrap();
+    let message = x25519::encrypt_with_rng(&[identity.recipient()], b"data", &mut rng).unwrap();
+
+    let mut tampered = message.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert!(x25519::decrypt(&identity, &tampered).is_err());
+    assert!(x25519::decrypt(&identity, &message[..100]).is_err());
+
+    let low_order = Recipient::from_bytes([0; 32]);
+    assert!(x25519::encrypt_with_rng(&[low_order], b"data", &mut rng).is_err());
+    assert!(x25519::encrypt_with_rng(&[], b"data", &mut rng).is_err());
+}
+
+#[test]
+fn generation_failures_are_reported() {
+    assert_kind!(Identity::generate_with_rng(&mut FailingRng), ErrorKind::KeyGenerationFailed(_));
+}